@@ -1,22 +1,37 @@
-use std::collections::{BTreeMap, HashMap};
-
-use crate::hash::{self, Hashable};
+use std::{
+    collections::{BTreeMap, HashMap},
+    marker::PhantomData,
+};
+
+use crate::{
+    digest::{Digest, Sha3_256},
+    hash::{Hashable, Label, NizHasher, NodeHasher},
+};
+
+const TUPLE: Label = Label::new("tuple");
+const LIST: Label = Label::new("list");
+const MAP: Label = Label::new("map");
+const OPTIONAL: Label = Label::new("optional");
+const RESULT: Label = Label::new("result");
+const OK: Label = Label::new("ok");
+const ERR: Label = Label::new("err");
+const UNORDERED_LIST: Label = Label::new("unordered_list");
+const PHANTOM: Label = Label::new("phantom");
 
 impl<A, B> Hashable for (A, B)
 where
     A: Hashable,
     B: Hashable,
 {
-    fn hash(&self) -> [u8; 32] {
-        use tiny_keccak::{Hasher, Sha3};
-
-        let mut output = [0u8; 32];
-        let mut hasher = Sha3::v256();
-        hasher.update(&hash::prefix("tuple"));
-        hasher.update(&self.0.hash());
-        hasher.update(&self.1.hash());
-        hasher.finalize(&mut output);
-        output
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        state.begin();
+        state.label(&TUPLE);
+        self.0.hash_into(state);
+        self.1.hash_into(state);
+        state.end();
     }
 }
 
@@ -24,30 +39,84 @@ impl<T> Hashable for [T]
 where
     T: Hashable,
 {
-    fn hash(&self) -> [u8; 32] {
-        use tiny_keccak::{Hasher, Sha3};
-
-        let mut output = [0u8; 32];
-        let mut hasher = Sha3::v256();
-        hasher.update(&hash::prefix("list"));
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        state.begin();
+        state.label(&LIST);
         if self.is_empty() {
-            hasher.update(&[0u8; 32]);
+            state.update(&[0u8; 32]);
         } else {
             for item in self {
-                hasher.update(&item.hash());
+                item.hash_into(state);
             }
         }
-        hasher.finalize(&mut output);
-        output
+        state.end();
     }
 }
 
+/// Hashes the items of a sequence as a multiset: the same items in any order
+/// give the same hash, while duplicates still count.
+///
+/// Item digests are sorted before they are combined, under their own
+/// `"unordered_list"` prefix so the result never collides with the ordered
+/// `[T]` encoding of the same items.
+pub fn hash_unordered<I>(items: I) -> [u8; 32]
+where
+    I: IntoIterator,
+    I::Item: Hashable,
+{
+    hash_unordered_with::<Sha3_256, _>(items)
+}
+
+/// Hashes `items` like `hash_unordered`, with `D` in place of SHA3-256.
+pub fn hash_unordered_with<D, I>(items: I) -> [u8; 32]
+where
+    D: Digest,
+    I: IntoIterator,
+    I::Item: Hashable,
+{
+    let mut state = NodeHasher::<D>::default();
+    hash_unordered_into(items, &mut state);
+    state.finalize()
+}
+
+/// Feeds `items` into `state` like `hash_unordered`, with the items hashed by
+/// the digest of `state`.
+pub fn hash_unordered_into<I, H>(items: I, state: &mut H)
+where
+    I: IntoIterator,
+    I::Item: Hashable,
+    H: NizHasher + ?Sized,
+{
+    let mut digests: Vec<[u8; 32]> = items
+        .into_iter()
+        .map(|item| item.hash_with::<H::Digest>())
+        .collect();
+    digests.sort_unstable();
+
+    state.begin();
+    state.label(&UNORDERED_LIST);
+    if digests.is_empty() {
+        state.update(&[0u8; 32]);
+    } else {
+        for digest in &digests {
+            state.update(digest);
+        }
+    }
+    state.end();
+}
+
 impl<T> Hashable for Vec<T>
 where
     T: Hashable,
 {
-    fn hash(&self) -> [u8; 32] {
-        <Vec<T> as AsRef<[T]>>::as_ref(self).hash()
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        <Vec<T> as AsRef<[T]>>::as_ref(self).hash_into(state)
     }
 }
 
@@ -56,21 +125,20 @@ where
     A: Hashable,
     B: Hashable,
 {
-    fn hash(&self) -> [u8; 32] {
-        use tiny_keccak::{Hasher, Sha3};
-
-        let mut output = [0u8; 32];
-        let mut hasher = Sha3::v256();
-        hasher.update(&hash::prefix("map"));
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        state.begin();
+        state.label(&MAP);
         if self.is_empty() {
-            hasher.update(&[0u8; 32]);
+            state.update(&[0u8; 32]);
         } else {
             for (key, value) in self {
-                hasher.update(&(key, value).hash());
+                (key, value).hash_into(state);
             }
         }
-        hasher.finalize(&mut output);
-        output
+        state.end();
     }
 }
 
@@ -79,8 +147,11 @@ where
     A: Hashable + Ord,
     B: Hashable,
 {
-    fn hash(&self) -> [u8; 32] {
-        self.iter().collect::<BTreeMap<_, _>>().hash()
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        self.iter().collect::<BTreeMap<_, _>>().hash_into(state)
     }
 }
 
@@ -88,23 +159,21 @@ impl<A> Hashable for Option<A>
 where
     A: Hashable,
 {
-    fn hash(&self) -> [u8; 32] {
-        use tiny_keccak::{Hasher, Sha3};
-
-        let mut output = [0u8; 32];
-        let mut hasher = Sha3::v256();
-        hasher.update(&hash::prefix("optional"));
-
-        let mut value_output = [0u8; 32];
-        if let Some(value) = self {
-            let mut value_hasher = Sha3::v256();
-            value_hasher.update(&value.hash());
-            value_hasher.finalize(&mut value_output);
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        state.begin();
+        state.label(&OPTIONAL);
+        match self {
+            Some(value) => {
+                state.begin();
+                value.hash_into(state);
+                state.end();
+            }
+            None => state.digest(&[0u8; 32]),
         }
-        hasher.update(&value_output);
-
-        hasher.finalize(&mut output);
-        output
+        state.end();
     }
 }
 
@@ -113,29 +182,40 @@ where
     A: Hashable,
     B: Hashable,
 {
-    fn hash(&self) -> [u8; 32] {
-        use tiny_keccak::{Hasher, Sha3};
-
-        let mut output = [0u8; 32];
-        let mut hasher = Sha3::v256();
-        hasher.update(&hash::prefix("result"));
-
-        let mut value_output = [0u8; 32];
-        let mut value_hasher = Sha3::v256();
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        state.begin();
+        state.label(&RESULT);
+        state.begin();
         match self {
             Ok(value) => {
-                value_hasher.update(&hash::prefix("ok"));
-                value_hasher.update(&value.hash());
+                state.label(&OK);
+                value.hash_into(state);
             }
             Err(e) => {
-                value_hasher.update(&hash::prefix("err"));
-                value_hasher.update(&e.hash());
+                state.label(&ERR);
+                e.hash_into(state);
             }
         }
-        value_hasher.finalize(&mut value_output);
+        state.end();
+        state.end();
+    }
+}
 
-        hasher.update(&value_output);
-        hasher.finalize(&mut output);
-        output
+/// Hashes to `sha3(prefix("phantom"))` whatever `T` is, so a marker field
+/// does not need to be skipped.
+impl<T> Hashable for PhantomData<T>
+where
+    T: ?Sized,
+{
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        state.begin();
+        state.label(&PHANTOM);
+        state.end();
     }
 }
@@ -0,0 +1,106 @@
+//! The digest algorithms niz's encoding can be computed with.
+//!
+//! SHA3-256 is always available and is what `Hashable::hash` uses. The others
+//! are behind the `keccak`, `sha2` and `blake3` features, and are chosen with
+//! `Hashable::hash_with`, e.g. `value.hash_with::<Blake3>()`.
+
+use crate::hash::Label;
+
+/// A hash function with a 32-byte output.
+pub trait Digest {
+    fn new() -> Self;
+    fn update(&mut self, bytes: &[u8]);
+    fn finalize(self) -> [u8; 32];
+
+    /// The digest of `label`'s name, which is fed into a node for each label.
+    fn prefix(label: &Label) -> [u8; 32]
+    where
+        Self: Sized,
+    {
+        let mut digest = Self::new();
+        digest.update(label.name().as_bytes());
+        digest.finalize()
+    }
+}
+
+/// SHA3-256, niz's default.
+pub struct Sha3_256(tiny_keccak::Sha3);
+
+impl Digest for Sha3_256 {
+    fn new() -> Self {
+        Self(tiny_keccak::Sha3::v256())
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        tiny_keccak::Hasher::update(&mut self.0, bytes);
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        let mut output = [0u8; 32];
+        tiny_keccak::Hasher::finalize(self.0, &mut output);
+        output
+    }
+
+    fn prefix(label: &Label) -> [u8; 32] {
+        *label.prefix()
+    }
+}
+
+/// Keccak-256, as used by Ethereum.
+#[cfg(feature = "keccak")]
+pub struct Keccak256(tiny_keccak::Keccak);
+
+#[cfg(feature = "keccak")]
+impl Digest for Keccak256 {
+    fn new() -> Self {
+        Self(tiny_keccak::Keccak::v256())
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        tiny_keccak::Hasher::update(&mut self.0, bytes);
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        let mut output = [0u8; 32];
+        tiny_keccak::Hasher::finalize(self.0, &mut output);
+        output
+    }
+}
+
+/// SHA-256.
+#[cfg(feature = "sha2")]
+pub struct Sha256(sha2::Sha256);
+
+#[cfg(feature = "sha2")]
+impl Digest for Sha256 {
+    fn new() -> Self {
+        Self(sha2::Digest::new())
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        sha2::Digest::update(&mut self.0, bytes);
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        sha2::Digest::finalize(self.0).into()
+    }
+}
+
+/// BLAKE3, with its default 32-byte output.
+#[cfg(feature = "blake3")]
+pub struct Blake3(blake3::Hasher);
+
+#[cfg(feature = "blake3")]
+impl Digest for Blake3 {
+    fn new() -> Self {
+        Self(blake3::Hasher::new())
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        self.0.finalize().into()
+    }
+}
@@ -1,14 +1,355 @@
+use crate::digest::{Digest, Sha3_256};
+
+/// A value with a stable content hash.
+///
+/// Implementors provide `hash_into`, which describes the value to a
+/// [`NizHasher`] as a tree of nodes. `hash`, `hash_with` and `hash_stream` are
+/// all built on it, so a value follows whichever digest and hasher it is
+/// hashed with. A value can still feed a precomputed digest as a node with
+/// `NizHasher::digest`, which is used as is whatever the digest.
+///
+/// `hash_into` and `hash_with` are generic, so `Hashable` is not dyn
+/// compatible and cannot be used as `dyn Hashable`.
 pub trait Hashable {
-    fn hash(&self) -> [u8; 32];
+    /// Hashes the value one digest per node, the compatibility encoding, with
+    /// a [`NodeHasher`] over SHA3-256.
+    fn hash(&self) -> [u8; 32] {
+        self.hash_with::<Sha3_256>()
+    }
+
+    /// Hashes the value like `hash`, with `D` in place of SHA3-256 for every
+    /// node and label.
+    fn hash_with<D>(&self) -> [u8; 32]
+    where
+        D: Digest,
+    {
+        let mut state = NodeHasher::<D>::default();
+        self.hash_into(&mut state);
+        state.finalize()
+    }
+
+    /// Feeds the value into `state` as a single node.
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized;
+
+    /// Hashes the value in a single pass with a [`StreamHasher`], which runs
+    /// far fewer permutations than `hash` for large values. The result is not
+    /// the same as `hash`.
+    fn hash_stream(&self) -> [u8; 32] {
+        let mut state = StreamHasher::new();
+        self.hash_into(&mut state);
+        state.finalize()
+    }
+}
+
+/// Receives a value as a tree of nodes, each made of labels, bytes and child
+/// nodes, and turns it into a digest.
+pub trait NizHasher {
+    /// The digest the hasher computes with.
+    type Digest: Digest;
+
+    /// Opens a child node of the current one.
+    fn begin(&mut self);
+
+    /// Feeds raw bytes into the current node.
+    fn update(&mut self, bytes: &[u8]);
+
+    /// Feeds a type, field or variant label into the current node.
+    fn label(&mut self, label: &Label);
+
+    /// Feeds a child node whose digest is already known.
+    fn digest(&mut self, digest: &[u8; 32]);
+
+    /// Closes the current node.
+    fn end(&mut self);
+}
+
+/// A type, field or variant label, together with its [`prefix`].
+///
+/// `Label::new` is a `const fn`, so a label declared as a constant has its
+/// prefix computed at compile time rather than on every hash.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Label {
+    name: &'static str,
+    prefix: [u8; 32],
+}
+
+impl Label {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            prefix: keccak_const::Sha3_256::new()
+                .update(name.as_bytes())
+                .finalize(),
+        }
+    }
+
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The same digest as `prefix(self.name())`.
+    pub const fn prefix(&self) -> &[u8; 32] {
+        &self.prefix
+    }
+}
+
+/// The compatibility [`NizHasher`]: every node is its own digest, which is
+/// fed into its parent, and every label `l` is fed as the digest of `l`.
+///
+/// Over SHA3-256, this is the encoding `Hashable::hash` has always produced.
+pub struct NodeHasher<D = Sha3_256> {
+    nodes: Vec<D>,
+    output: Option<[u8; 32]>,
+}
+
+impl NodeHasher {
+    /// Creates a hasher over SHA3-256. `NodeHasher::<D>::default()` creates one
+    /// over any other digest.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<D> NodeHasher<D>
+where
+    D: Digest,
+{
+    /// Returns the digest of the outermost node.
+    ///
+    /// Panics if no node has been closed yet.
+    pub fn finalize(self) -> [u8; 32] {
+        self.output.expect("no node was hashed")
+    }
+
+    fn current(&mut self) -> &mut D {
+        self.nodes.last_mut().expect("no open node")
+    }
+}
+
+impl<D> Default for NodeHasher<D>
+where
+    D: Digest,
+{
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            output: None,
+        }
+    }
+}
+
+impl<D> NizHasher for NodeHasher<D>
+where
+    D: Digest,
+{
+    type Digest = D;
+
+    fn begin(&mut self) {
+        self.nodes.push(D::new());
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.current().update(bytes);
+    }
+
+    fn label(&mut self, label: &Label) {
+        self.current().update(&D::prefix(label));
+    }
+
+    fn digest(&mut self, digest: &[u8; 32]) {
+        match self.nodes.last_mut() {
+            Some(node) => node.update(digest),
+            None => self.output = Some(*digest),
+        }
+    }
+
+    fn end(&mut self) {
+        let node = self.nodes.pop().expect("no open node");
+        self.digest(&node.finalize());
+    }
+}
+
+/// The streaming [`NizHasher`]: the whole tree is framed into one byte stream
+/// that is hashed by a single digest.
+///
+/// `begin` and `end` are written as the bytes `1` and `2`, `update` and
+/// `label` as `0` and `3` followed by the length as a big-endian `u64` and
+/// the bytes, and `digest` as `4` followed by the digest.
+pub struct StreamHasher<D = Sha3_256> {
+    digest: D,
+}
+
+impl StreamHasher {
+    /// Creates a hasher over SHA3-256. `StreamHasher::<D>::default()` creates one
+    /// over any other digest.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<D> StreamHasher<D>
+where
+    D: Digest,
+{
+    pub fn finalize(self) -> [u8; 32] {
+        self.digest.finalize()
+    }
+
+    fn write(&mut self, tag: u8, bytes: &[u8]) {
+        self.digest.update(&[tag]);
+        self.digest.update(&(bytes.len() as u64).to_be_bytes());
+        self.digest.update(bytes);
+    }
+}
+
+impl<D> Default for StreamHasher<D>
+where
+    D: Digest,
+{
+    fn default() -> Self {
+        Self { digest: D::new() }
+    }
+}
+
+impl<D> NizHasher for StreamHasher<D>
+where
+    D: Digest,
+{
+    type Digest = D;
+
+    fn begin(&mut self) {
+        self.digest.update(&[1]);
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.write(0, bytes);
+    }
+
+    fn label(&mut self, label: &Label) {
+        self.write(3, label.name().as_bytes());
+    }
+
+    fn digest(&mut self, digest: &[u8; 32]) {
+        self.digest.update(&[4]);
+        self.digest.update(digest);
+    }
+
+    fn end(&mut self) {
+        self.digest.update(&[2]);
+    }
+}
+
+/// A function that `#[niz(with = "...")]` hashes a field with.
+///
+/// Implemented for any `fn(&T) -> [u8; 32]`, whose digest is fed into `state`
+/// as is, and for any `fn(&T, &mut H)` that feeds the value into `state`
+/// itself, like `Hashable::hash_into`, and so follows its digest. `Marker` is
+/// [`DigestFn`] or [`HashIntoFn`], and only keeps the two impls apart.
+pub trait HashFn<T, H, Marker>
+where
+    T: ?Sized,
+    H: NizHasher + ?Sized,
+{
+    fn hash_value(&self, value: &T, state: &mut H);
+}
+
+/// Marks the [`HashFn`] impl for functions that return a digest.
+pub enum DigestFn {}
+
+/// Marks the [`HashFn`] impl for functions that feed a hasher.
+pub enum HashIntoFn {}
+
+impl<T, H, F> HashFn<T, H, DigestFn> for F
+where
+    T: ?Sized,
+    H: NizHasher + ?Sized,
+    F: Fn(&T) -> [u8; 32],
+{
+    fn hash_value(&self, value: &T, state: &mut H) {
+        state.digest(&self(value));
+    }
+}
+
+impl<T, H, F> HashFn<T, H, HashIntoFn> for F
+where
+    T: ?Sized,
+    H: NizHasher + ?Sized,
+    F: Fn(&T, &mut H),
+{
+    fn hash_value(&self, value: &T, state: &mut H) {
+        self(value, state);
+    }
+}
+
+/// Feeds the fields of a struct into a hasher, without the type label.
+///
+/// Derived for structs, so that `#[niz(flatten)]` can hash a nested struct as
+/// if its fields were declared inline in the parent.
+pub trait HashableFields {
+    fn hash_fields<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized;
+}
+
+/// Hashes named projections of a value, each over a subset of its fields.
+///
+/// Derived for structs with `#[niz(profile = "...")]` fields.
+pub trait HashableProfiles {
+    /// Hashes the fields in `profile`, or returns `None` if the type has no
+    /// profile by that name.
+    fn hash_profile(&self, profile: &str) -> Option<[u8; 32]> {
+        self.hash_profile_with::<Sha3_256>(profile)
+    }
+
+    /// Hashes the fields in `profile` like `hash_profile`, with `D` in place
+    /// of SHA3-256.
+    fn hash_profile_with<D>(&self, profile: &str) -> Option<[u8; 32]>
+    where
+        D: Digest;
+}
+
+/// A value that stores its own content hash in one of its fields.
+///
+/// Derived for structs with a `#[niz(self_hash)]` field, which is left out of
+/// the hash it holds.
+pub trait SelfHashable: Hashable {
+    /// Stores the current hash in the self-hash field.
+    fn seal(&mut self) {
+        self.seal_with::<Sha3_256>()
+    }
+
+    /// Checks that the self-hash field holds the current hash.
+    fn verify_self_hash(&self) -> bool {
+        self.verify_self_hash_with::<Sha3_256>()
+    }
+
+    /// Stores the current `hash_with::<D>()` in the self-hash field.
+    fn seal_with<D>(&mut self)
+    where
+        D: Digest;
+
+    /// Checks that the self-hash field holds the current `hash_with::<D>()`.
+    fn verify_self_hash_with<D>(&self) -> bool
+    where
+        D: Digest;
 }
 
 impl<T> Hashable for &T
 where
-    T: Hashable,
+    T: Hashable + ?Sized,
 {
     fn hash(&self) -> [u8; 32] {
         (*self).hash()
     }
+
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        (*self).hash_into(state)
+    }
 }
 
 pub fn prefix(label: impl AsRef<str>) -> [u8; 32] {
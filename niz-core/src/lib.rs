@@ -1,7 +1,10 @@
 pub mod adt;
+pub mod digest;
 pub mod hash;
 pub mod scalar;
+pub mod schema;
 
 pub use hash::*;
 
+pub use serde_json;
 pub use tiny_keccak;
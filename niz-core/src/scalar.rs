@@ -1,24 +1,28 @@
 use std::collections::BTreeMap;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, Timelike, Utc};
 use serde_json::{Map, Value};
+use unicode_normalization::UnicodeNormalization;
 use uuid::Uuid;
 
-use crate::hash::{self, Hashable};
+use crate::{
+    digest::{Digest, Sha3_256},
+    hash::{Hashable, Label, NizHasher, NodeHasher},
+};
 
+const STRING: Label = Label::new("string");
+const DATETIME: Label = Label::new("datetime");
 macro_rules! impl_hashable_for_to_be_bytes {
     ($($t:ty),*) => {
         $(
             impl Hashable for $t {
-                fn hash(&self) -> [u8; 32] {
-                    use tiny_keccak::{Hasher, Sha3};
-
-                    let mut output = [0u8; 32];
-                    let mut hasher = Sha3::v256();
-                    hasher.update(&self.to_be_bytes());
-                    hasher.finalize(&mut output);
-
-                    output
+                fn hash_into<H>(&self, state: &mut H)
+                where
+                    H: NizHasher + ?Sized,
+                {
+                    state.begin();
+                    state.update(&self.to_be_bytes());
+                    state.end();
                 }
             }
         )*
@@ -41,97 +45,337 @@ impl_hashable_for_to_be_bytes!(f32);
 impl_hashable_for_to_be_bytes!(f64);
 
 impl Hashable for bool {
-    fn hash(&self) -> [u8; 32] {
-        use tiny_keccak::{Hasher, Sha3};
-
-        let mut output = [0u8; 32];
-        let mut hasher = Sha3::v256();
-        hasher.update(if *self { &[1u8] } else { &[0u8] });
-        hasher.finalize(&mut output);
-
-        output
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        state.begin();
+        state.update(if *self { &[1u8] } else { &[0u8] });
+        state.end();
     }
 }
 
 impl Hashable for str {
-    fn hash(&self) -> [u8; 32] {
-        use tiny_keccak::{Hasher, Sha3};
-
-        let mut val_output = [0u8; 32];
-        let mut val_hasher = Sha3::v256();
-        val_hasher.update(self.as_bytes());
-        val_hasher.finalize(&mut val_output);
-
-        let mut output = [0u8; 32];
-        let mut hasher = Sha3::v256();
-        hasher.update(&hash::prefix("string"));
-        hasher.update(&val_output);
-        hasher.finalize(&mut output);
-        output
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        state.begin();
+        state.label(&STRING);
+        state.begin();
+        state.update(self.as_bytes());
+        state.end();
+        state.end();
     }
 }
 
 impl Hashable for String {
-    fn hash(&self) -> [u8; 32] {
-        <String as AsRef<str>>::as_ref(self).hash()
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        <String as AsRef<str>>::as_ref(self).hash_into(state)
     }
 }
 
 impl Hashable for Uuid {
-    fn hash(&self) -> [u8; 32] {
-        use tiny_keccak::{Hasher, Sha3};
-
-        let mut output = [0u8; 32];
-        let mut hasher = Sha3::v256();
-        hasher.update(self.as_bytes());
-        hasher.finalize(&mut output);
-        output
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        state.begin();
+        state.update(self.as_bytes());
+        state.end();
     }
 }
 
 impl Hashable for DateTime<Utc> {
-    fn hash(&self) -> [u8; 32] {
-        use tiny_keccak::{Hasher, Sha3};
-
-        let mut val_output = [0u8; 32];
-        let mut val_hasher = Sha3::v256();
-        val_hasher.update(self.to_rfc3339().as_bytes());
-        val_hasher.finalize(&mut val_output);
-
-        let mut output = [0u8; 32];
-        let mut hasher = Sha3::v256();
-        hasher.update(&hash::prefix("datetime"));
-        hasher.update(&val_output);
-        hasher.finalize(&mut output);
-        output
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        hash_datetime_str(&self.to_rfc3339(), state);
     }
 }
 
+/// Feeds a formatted timestamp into `state` the way `DateTime<Utc>` does.
+fn hash_datetime_str<H>(value: &str, state: &mut H)
+where
+    H: NizHasher + ?Sized,
+{
+    state.begin();
+    state.label(&DATETIME);
+    state.begin();
+    state.update(value.as_bytes());
+    state.end();
+    state.end();
+}
+
 impl Hashable for Map<String, Value> {
-    fn hash(&self) -> [u8; 32] {
-        self.iter().collect::<BTreeMap<_, _>>().hash()
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        self.iter().collect::<BTreeMap<_, _>>().hash_into(state)
     }
 }
 
 impl Hashable for Value {
-    fn hash(&self) -> [u8; 32] {
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
         match self {
-            Value::Null => [0u8; 32],
-            Value::Bool(val) => val.hash(),
+            Value::Null => state.digest(&[0u8; 32]),
+            Value::Bool(val) => val.hash_into(state),
             Value::Number(val) => {
                 if let Some(val) = val.as_u64() {
-                    val.hash()
+                    val.hash_into(state)
                 } else if let Some(val) = val.as_i64() {
-                    val.hash()
+                    val.hash_into(state)
                 } else if let Some(val) = val.as_f64() {
-                    val.hash()
+                    val.hash_into(state)
                 } else {
                     panic!("unsupported number type")
                 }
             }
-            Value::String(val) => val.hash(),
-            Value::Array(val) => val.hash(),
-            Value::Object(val) => val.hash(),
+            Value::String(val) => val.hash_into(state),
+            Value::Array(val) => val.hash_into(state),
+            Value::Object(val) => val.hash_into(state),
+        }
+    }
+}
+
+/// A normalization applied to a string before it is hashed, so that values a
+/// user would consider equal hash the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Normalization {
+    /// Unicode Normalization Form C, so that precomposed and decomposed
+    /// characters agree.
+    Nfc,
+    /// Unicode lowercase.
+    Lowercase,
+    /// Leading and trailing whitespace removed.
+    Trim,
+}
+
+impl Normalization {
+    /// The name of the normalization in `#[niz(normalize = "...")]`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Normalization::Nfc => "nfc",
+            Normalization::Lowercase => "lowercase",
+            Normalization::Trim => "trim",
+        }
+    }
+
+    pub fn apply(self, value: &str) -> String {
+        match self {
+            Normalization::Nfc => value.nfc().collect(),
+            Normalization::Lowercase => value.to_lowercase(),
+            Normalization::Trim => value.trim().to_owned(),
         }
     }
 }
+
+/// Hashes a string like `str` does, after applying each of `normalizations` in
+/// order.
+pub fn hash_normalized(value: impl AsRef<str>, normalizations: &[Normalization]) -> [u8; 32] {
+    hash_normalized_with::<Sha3_256>(value, normalizations)
+}
+
+/// Hashes a string like `hash_normalized`, with `D` in place of SHA3-256.
+pub fn hash_normalized_with<D>(value: impl AsRef<str>, normalizations: &[Normalization]) -> [u8; 32]
+where
+    D: Digest,
+{
+    normalize(value, normalizations).hash_with::<D>()
+}
+
+/// Applies `normalizations` to `value` in order.
+pub fn normalize(value: impl AsRef<str>, normalizations: &[Normalization]) -> String {
+    normalizations
+        .iter()
+        .fold(value.as_ref().to_owned(), |value, normalization| {
+            normalization.apply(&value)
+        })
+}
+
+/// A float type that can be quantized before it is hashed.
+pub trait Float: Copy + Hashable {
+    /// The NaN every NaN is quantized to.
+    const NAN: Self;
+
+    fn to_f64(self) -> f64;
+    fn from_f64(value: f64) -> Self;
+}
+
+impl Float for f32 {
+    const NAN: Self = f32::NAN;
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+impl Float for f64 {
+    const NAN: Self = f64::NAN;
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+/// How a float is quantized before it is hashed, so that values that differ
+/// only by numerical noise hash the same.
+///
+/// Every quantization also maps `-0.0` to `0.0` and every NaN to the same
+/// NaN.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quantization {
+    /// Only canonical zero and NaN.
+    Canonical,
+    /// Rounded to this many decimal places.
+    Round(u32),
+    /// Rounded to this many significant digits.
+    SignificantDigits(u32),
+}
+
+impl Quantization {
+    /// The most decimal places or significant digits a quantization can keep.
+    /// 17 significant digits are enough to tell every pair of `f64` apart.
+    pub const MAX_DIGITS: u32 = 17;
+
+    /// Quantizes `value`. Rounding goes through the exact decimal expansion of
+    /// the value, with halfway cases rounded to even, so the result is the same
+    /// on every target. Rust's float formatting only rounds this way since
+    /// 1.67, and the tests pin it.
+    ///
+    /// Panics if a `Round` or `SignificantDigits` is above `MAX_DIGITS`, or a
+    /// `SignificantDigits` is 0, the same bounds the derive enforces.
+    pub fn apply<F: Float>(self, value: F) -> F {
+        if let Quantization::Round(digits) | Quantization::SignificantDigits(digits) = self {
+            assert!(
+                digits <= Self::MAX_DIGITS,
+                "cannot quantize to more than {} digits",
+                Self::MAX_DIGITS
+            );
+        }
+        if let Quantization::SignificantDigits(digits) = self {
+            assert!(digits >= 1, "cannot quantize to 0 significant digits");
+        }
+        let value = value.to_f64();
+        if value.is_nan() {
+            return F::NAN;
+        }
+        let value = F::from_f64(match self {
+            _ if value.is_infinite() => value,
+            Quantization::Canonical => value,
+            Quantization::Round(places) => {
+                format!("{:.*}", places as usize, value).parse().unwrap()
+            }
+            Quantization::SignificantDigits(digits) => {
+                format!("{:.*e}", (digits - 1) as usize, value)
+                    .parse()
+                    .unwrap()
+            }
+        });
+        // `-0.0 == 0.0`, so this also catches values rounded to `-0.0`
+        if value.to_f64() == 0.0 {
+            F::from_f64(0.0)
+        } else {
+            value
+        }
+    }
+}
+
+/// Hashes a float like `f32` or `f64` do, after quantizing it.
+pub fn hash_quantized<F: Float>(value: F, quantization: Quantization) -> [u8; 32] {
+    hash_quantized_with::<Sha3_256, F>(value, quantization)
+}
+
+/// Hashes a float like `hash_quantized`, with `D` in place of SHA3-256.
+pub fn hash_quantized_with<D, F>(value: F, quantization: Quantization) -> [u8; 32]
+where
+    D: Digest,
+    F: Float,
+{
+    quantization.apply(value).hash_with::<D>()
+}
+
+/// The precision a timestamp is truncated to before it is hashed, so that the
+/// same instant read back from stores with different precisions hashes the
+/// same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimePrecision {
+    Seconds,
+    Millis,
+    Micros,
+    Nanos,
+}
+
+impl TimePrecision {
+    /// The name of the precision in `#[niz(truncate = "...")]`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TimePrecision::Seconds => "s",
+            TimePrecision::Millis => "ms",
+            TimePrecision::Micros => "us",
+            TimePrecision::Nanos => "ns",
+        }
+    }
+
+    /// Truncates `value` towards the past.
+    pub fn apply(self, value: &DateTime<Utc>) -> DateTime<Utc> {
+        let unit = match self {
+            TimePrecision::Seconds => 1_000_000_000,
+            TimePrecision::Millis => 1_000_000,
+            TimePrecision::Micros => 1_000,
+            TimePrecision::Nanos => 1,
+        };
+        let nanos = value.nanosecond();
+        value.with_nanosecond(nanos - nanos % unit).unwrap()
+    }
+}
+
+/// Hashes a timestamp after truncating it to `precision`.
+///
+/// Unlike `DateTime<Utc>::hash`, this formats the timestamp with exactly as
+/// many fractional digits as `precision` has and a `Z` offset, e.g.
+/// `2024-01-01T00:00:00.120Z` for milliseconds, so the encoding does not
+/// depend on how `to_rfc3339` chooses to format the fraction.
+pub fn hash_truncated(value: &DateTime<Utc>, precision: TimePrecision) -> [u8; 32] {
+    hash_truncated_with::<Sha3_256>(value, precision)
+}
+
+/// Hashes a timestamp like `hash_truncated`, with `D` in place of SHA3-256.
+pub fn hash_truncated_with<D>(value: &DateTime<Utc>, precision: TimePrecision) -> [u8; 32]
+where
+    D: Digest,
+{
+    let mut state = NodeHasher::<D>::default();
+    hash_truncated_into(value, precision, &mut state);
+    state.finalize()
+}
+
+/// Feeds a timestamp into `state` like `hash_truncated`.
+pub fn hash_truncated_into<H>(value: &DateTime<Utc>, precision: TimePrecision, state: &mut H)
+where
+    H: NizHasher + ?Sized,
+{
+    let format = match precision {
+        TimePrecision::Seconds => SecondsFormat::Secs,
+        TimePrecision::Millis => SecondsFormat::Millis,
+        TimePrecision::Micros => SecondsFormat::Micros,
+        TimePrecision::Nanos => SecondsFormat::Nanos,
+    };
+    hash_datetime_str(&precision.apply(value).to_rfc3339_opts(format, true), state);
+}
@@ -0,0 +1,221 @@
+//! Structured descriptions of what a derived `Hashable` impl commits to.
+//!
+//! The derive also implements [`HashSchema`], so the encoding of a type can be
+//! reviewed, or reimplemented in another language, without reading the
+//! generated code. [`Schema::to_json`] exports it.
+
+use std::marker::PhantomData;
+
+use serde_json::{json, Value};
+
+use crate::scalar::{Normalization, Quantization, TimePrecision};
+
+/// Describes how a type is hashed.
+///
+/// Derived alongside `Hashable`.
+pub trait HashSchema {
+    fn hash_schema() -> Schema;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Schema {
+    /// Hashes to `sha3(prefix(label) || field_0 || field_1 || ...)`, with the
+    /// fields in the order listed.
+    Struct {
+        label: &'static str,
+        fields: Vec<FieldSchema>,
+    },
+    /// Hashes exactly like its only hashed field.
+    Transparent { field: FieldSchema },
+    /// Hashes to `sha3(prefix(label) || variant)`, where `variant` is
+    /// `sha3(prefix(variant label) || discriminant.hash() || field_0 || ...)`
+    /// and the discriminant is hashed as `repr`.
+    Enum {
+        label: &'static str,
+        repr: &'static str,
+        variants: Vec<VariantSchema>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VariantSchema {
+    pub label: &'static str,
+    /// The discriminant in decimal, since it can be any integer type.
+    pub discriminant: String,
+    pub fields: Vec<FieldSchema>,
+}
+
+/// A single field, hashed as `sha3(prefix(label) || digest)` unless it is
+/// flattened.
+///
+/// Two field schemas are equal when their nested schemas are, whichever
+/// function produces them.
+#[derive(Clone, Debug)]
+pub struct FieldSchema {
+    pub label: &'static str,
+    /// The Rust type of the field.
+    pub ty: &'static str,
+    /// The schema of the field's type, for `Hashable` and `Flatten` fields
+    /// whose type implements `HashSchema`. Fields whose type depends on a type
+    /// parameter have none, since the derive cannot tell.
+    pub nested: Option<fn() -> Schema>,
+    pub encoding: Encoding,
+    pub skip: Skip,
+    /// The profiles the field is hashed in.
+    pub profiles: Vec<&'static str>,
+}
+
+/// How the digest of a field value is computed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// `value.hash()`.
+    Hashable,
+    /// `path(&value)`, or `path(&value, state)` for a `path` that feeds the
+    /// hasher itself.
+    With(&'static str),
+    /// The hash of the value serialized to a `serde_json::Value`.
+    Json,
+    /// `niz::adt::hash_unordered(&value)`.
+    Unordered,
+    /// The field digests of the value are fed straight into the parent.
+    Flatten,
+    /// `niz::scalar::hash_normalized(&value, normalizations)`.
+    Normalized(Vec<Normalization>),
+    /// `niz::scalar::hash_quantized(value, quantization)`.
+    Quantized(Quantization),
+    /// `niz::scalar::hash_truncated(&value, precision)`.
+    Truncated(TimePrecision),
+}
+
+/// When a field is left out of the hash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Skip {
+    Never,
+    Always,
+    /// Whenever `path(&value)` returns `true`.
+    If(&'static str),
+    /// Whenever the value equals `Default::default()`.
+    IfDefault,
+    /// Whenever the value is `None`.
+    IfNone,
+}
+
+impl Schema {
+    pub fn to_json(&self) -> Value {
+        match self {
+            Schema::Struct { label, fields } => json!({
+                "kind": "struct",
+                "label": label,
+                "fields": fields.iter().map(FieldSchema::to_json).collect::<Vec<_>>(),
+            }),
+            Schema::Transparent { field } => json!({
+                "kind": "transparent",
+                "field": field.to_json(),
+            }),
+            Schema::Enum {
+                label,
+                repr,
+                variants,
+            } => json!({
+                "kind": "enum",
+                "label": label,
+                "repr": repr,
+                "variants": variants.iter().map(VariantSchema::to_json).collect::<Vec<_>>(),
+            }),
+        }
+    }
+}
+
+impl VariantSchema {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "label": self.label,
+            "discriminant": self.discriminant,
+            "fields": self.fields.iter().map(FieldSchema::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl PartialEq for FieldSchema {
+    fn eq(&self, other: &Self) -> bool {
+        self.label == other.label
+            && self.ty == other.ty
+            && self.nested.map(|schema| schema()) == other.nested.map(|schema| schema())
+            && self.encoding == other.encoding
+            && self.skip == other.skip
+            && self.profiles == other.profiles
+    }
+}
+
+impl Eq for FieldSchema {}
+
+impl FieldSchema {
+    pub fn to_json(&self) -> Value {
+        let encoding = match &self.encoding {
+            Encoding::Hashable => json!("hashable"),
+            Encoding::With(path) => json!({ "with": path }),
+            Encoding::Json => json!("json"),
+            Encoding::Unordered => json!("unordered"),
+            Encoding::Flatten => json!("flatten"),
+            Encoding::Normalized(normalizations) => json!({
+                "normalize": normalizations
+                    .iter()
+                    .map(|normalization| normalization.as_str())
+                    .collect::<Vec<_>>(),
+            }),
+            Encoding::Quantized(Quantization::Canonical) => json!("canonical_float"),
+            Encoding::Quantized(Quantization::Round(places)) => json!({ "round": places }),
+            Encoding::Quantized(Quantization::SignificantDigits(digits)) => {
+                json!({ "significant_digits": digits })
+            }
+            Encoding::Truncated(precision) => json!({ "truncate": precision.as_str() }),
+        };
+        let skip = match self.skip {
+            Skip::Never => json!("never"),
+            Skip::Always => json!("always"),
+            Skip::If(path) => json!({ "if": path }),
+            Skip::IfDefault => json!("if_default"),
+            Skip::IfNone => json!("if_none"),
+        };
+        json!({
+            "label": self.label,
+            "type": self.ty,
+            "nested": self.nested.map(|schema| schema().to_json()),
+            "encoding": encoding,
+            "skip": skip,
+            "profiles": self.profiles,
+        })
+    }
+}
+
+/// Finds the schema of a field type, if it has one, for the derive.
+///
+/// `(&NestedSchema::<T>(PhantomData)).nested_schema()` resolves to the
+/// [`ViaHashSchema`] impl when `T: HashSchema`, and to the [`ViaNoSchema`]
+/// impl on `&NestedSchema<T>` otherwise, since that one takes another
+/// auto-reference to reach.
+#[doc(hidden)]
+pub struct NestedSchema<T: ?Sized>(pub PhantomData<T>);
+
+#[doc(hidden)]
+pub trait ViaHashSchema {
+    fn nested_schema(&self) -> Option<fn() -> Schema>;
+}
+
+impl<T> ViaHashSchema for NestedSchema<T>
+where
+    T: HashSchema + ?Sized,
+{
+    fn nested_schema(&self) -> Option<fn() -> Schema> {
+        Some(T::hash_schema)
+    }
+}
+
+#[doc(hidden)]
+pub trait ViaNoSchema {
+    fn nested_schema(&self) -> Option<fn() -> Schema> {
+        None
+    }
+}
+
+impl<T> ViaNoSchema for &NestedSchema<T> where T: ?Sized {}
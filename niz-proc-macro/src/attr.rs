@@ -0,0 +1,615 @@
+use syn::{
+    ext::IdentExt, parse_quote, punctuated::Punctuated, Attribute, Error, ExprPath, Ident, Lit,
+    LitInt, LitStr, Meta, NestedMeta, Path, Result, Token, WherePredicate,
+};
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+
+use crate::{case::RenameRule, util};
+
+/// The limit of `niz::scalar::Quantization::MAX_DIGITS` on `round` and
+/// `significant_digits`.
+const MAX_DIGITS: u32 = 17;
+
+/// Options set by `#[niz(...)]` on a struct or enum.
+#[derive(Default)]
+pub(crate) struct Container {
+    /// The label the type is hashed under, in place of its Rust name.
+    pub name: Option<LitStr>,
+    /// Where-predicates that replace the inferred `Hashable` bounds.
+    pub bound: Option<Vec<WherePredicate>>,
+    /// Hash a single-field struct exactly like its only field.
+    pub transparent: bool,
+    /// Also read the type's `#[serde(...)]` attributes.
+    pub serde_compat: bool,
+    /// From `#[serde(rename_all = "...")]`, with `serde_compat`. Renames the
+    /// fields of a struct, or the variants of an enum.
+    pub rename_all: Option<RenameRule>,
+    /// From `#[serde(rename_all_fields = "...")]`, with `serde_compat`.
+    /// Renames the fields of every enum variant.
+    pub rename_all_fields: Option<RenameRule>,
+    /// The path generated code reaches niz through, in place of `::niz`.
+    pub krate: Option<Path>,
+    /// Hash fields in the order of their labels, not their declaration.
+    pub sorted_fields: bool,
+    /// The foreign type this type mirrors, which gets a hashing function in
+    /// place of a `Hashable` impl.
+    pub remote: Option<Path>,
+}
+
+impl Container {
+    pub fn from_attrs(attrs: &[Attribute]) -> Result<Self> {
+        let mut container = Container::default();
+        for meta in niz_metas(attrs)? {
+            match &meta {
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("name") =>
+                {
+                    check_unset(&meta, container.name.is_some())?;
+                    container.name = Some(get_lit_str(&name_value.lit)?.clone());
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("bound") =>
+                {
+                    check_unset(&meta, container.bound.is_some())?;
+                    let bound = get_lit_str(&name_value.lit)?
+                        .parse_with(Punctuated::<WherePredicate, Token![,]>::parse_terminated)?;
+                    container.bound = Some(bound.into_iter().collect());
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("transparent") => {
+                    check_unset(&meta, container.transparent)?;
+                    container.transparent = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("serde_compat") => {
+                    check_unset(&meta, container.serde_compat)?;
+                    container.serde_compat = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("sorted_fields") => {
+                    check_unset(&meta, container.sorted_fields)?;
+                    container.sorted_fields = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("crate") =>
+                {
+                    check_unset(&meta, container.krate.is_some())?;
+                    container.krate = Some(get_lit_str(&name_value.lit)?.parse()?);
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("remote") =>
+                {
+                    check_unset(&meta, container.remote.is_some())?;
+                    container.remote = Some(get_lit_str(&name_value.lit)?.parse()?);
+                }
+                _ => return Err(unknown(&meta, "container")),
+            }
+            check_exclusive(
+                &meta,
+                &[
+                    ("name", container.name.is_some()),
+                    ("transparent", container.transparent),
+                ],
+            )?;
+        }
+
+        if container.serde_compat {
+            for meta in serde_metas(attrs) {
+                let path = meta.path();
+                if path.is_ident("rename") {
+                    if container.name.is_none() && !container.transparent {
+                        container.name = serde_name(&meta);
+                    }
+                } else if path.is_ident("rename_all") {
+                    container.rename_all = serde_rename_rule(&meta)?;
+                } else if path.is_ident("rename_all_fields") {
+                    container.rename_all_fields = serde_rename_rule(&meta)?;
+                }
+            }
+        }
+
+        Ok(container)
+    }
+
+    /// The path to the niz crate in generated code.
+    pub fn krate(&self) -> Path {
+        self.krate.clone().unwrap_or_else(|| parse_quote! { ::niz })
+    }
+
+    /// The expression generated code reads the hashed value through: the
+    /// argument of the hashing function for remote types, or else `self`.
+    pub fn receiver(&self) -> TokenStream2 {
+        match &self.remote {
+            Some(_) => quote! { value },
+            None => quote! { self },
+        }
+    }
+}
+
+/// Options set by `#[niz(...)]` on an enum variant.
+#[derive(Default)]
+pub(crate) struct Variant {
+    /// The label the variant is hashed under, in place of its Rust name.
+    pub rename: Option<LitStr>,
+    /// The discriminant the variant is hashed with, in place of its own.
+    pub index: Option<LitInt>,
+    /// From the variant's `#[serde(rename_all = "...")]`, with
+    /// `serde_compat`. Renames its fields.
+    pub rename_all: Option<RenameRule>,
+}
+
+impl Variant {
+    pub fn from_variant(variant: &syn::Variant, container: &Container) -> Result<Self> {
+        let mut attrs = Variant::default();
+        for meta in niz_metas(&variant.attrs)? {
+            match &meta {
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("rename") =>
+                {
+                    check_unset(&meta, attrs.rename.is_some())?;
+                    attrs.rename = Some(get_lit_str(&name_value.lit)?.clone());
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("index") =>
+                {
+                    check_unset(&meta, attrs.index.is_some())?;
+                    attrs.index = Some(get_lit_int(&name_value.lit)?.clone());
+                }
+                _ => return Err(unknown(&meta, "variant")),
+            }
+        }
+
+        if container.serde_compat {
+            let metas = serde_metas(&variant.attrs);
+            if attrs.rename.is_none() {
+                attrs.rename = metas
+                    .iter()
+                    .filter(|meta| meta.path().is_ident("rename"))
+                    .find_map(serde_name);
+                if let (None, Some(rule)) = (&attrs.rename, container.rename_all) {
+                    let name = rule.apply_to_variant(&variant.ident.unraw().to_string());
+                    attrs.rename = Some(LitStr::new(&name, variant.ident.span()));
+                }
+            }
+            if let Some(meta) = metas.iter().find(|meta| meta.path().is_ident("rename_all")) {
+                attrs.rename_all = serde_rename_rule(meta)?;
+            }
+        }
+
+        Ok(attrs)
+    }
+}
+
+/// Options set by `#[niz(...)]` on a struct or variant field.
+#[derive(Default)]
+pub(crate) struct Field {
+    /// Leave the field out of the hash.
+    pub skip: bool,
+    /// Hash the field through its `serde_json::Value` representation.
+    pub json: bool,
+    /// The label the field is hashed under, in place of its Rust name.
+    pub rename: Option<LitStr>,
+    /// A `fn(&T) -> [u8; 32]` or `fn(&T, &mut H)` that hashes the field in
+    /// place of `Hashable`.
+    pub with: Option<ExprPath>,
+    /// Leave the field out of the hash when it holds a neutral value.
+    pub skip_if: Option<SkipIf>,
+    /// Hash the fields of the nested struct as if they were declared inline.
+    pub flatten: bool,
+    /// Hash a sequence as a multiset, ignoring the order of its items.
+    pub unordered: bool,
+    /// The named profiles whose hash includes the field.
+    pub profiles: Vec<LitStr>,
+    /// Holds the struct's own hash. Implies `skip`.
+    pub self_hash: bool,
+    /// The `niz::scalar::Normalization` variants applied, in order, to a
+    /// string field before it is hashed.
+    pub normalize: Vec<Ident>,
+    /// How a float field is quantized before it is hashed.
+    pub quantize: Option<Quantize>,
+    /// The `niz::scalar::TimePrecision` variant a timestamp field is truncated
+    /// to before it is hashed.
+    pub truncate: Option<Ident>,
+}
+
+/// How a float field is quantized, mirroring `niz::scalar::Quantization`.
+pub(crate) enum Quantize {
+    /// `#[niz(canonical_float)]`: only canonical zero and NaN.
+    Canonical,
+    /// `#[niz(round = N)]`: rounded to `N` decimal places.
+    Round(u32),
+    /// `#[niz(significant_digits = N)]`: rounded to `N` significant digits.
+    SignificantDigits(u32),
+}
+
+/// When a field is left out of the hash.
+pub(crate) enum SkipIf {
+    /// `#[niz(skip_if = "path")]`: when `path(&value)` returns `true`.
+    Path(ExprPath),
+    /// `#[niz(skip_if_default)]`: when the value equals `Default::default()`.
+    Default,
+    /// `#[niz(skip_if_none)]`: when the value is `None`.
+    None,
+}
+
+impl Field {
+    /// Parses the options of `syn_field`, which serde would rename by
+    /// `rename_all`.
+    pub fn from_field(
+        syn_field: &syn::Field,
+        container: &Container,
+        rename_all: Option<RenameRule>,
+    ) -> Result<Self> {
+        let mut field = Field::default();
+        for meta in niz_metas(&syn_field.attrs)? {
+            match &meta {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                    check_unset(&meta, field.skip)?;
+                    field.skip = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("json") => {
+                    check_unset(&meta, field.json)?;
+                    field.json = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("rename") =>
+                {
+                    check_unset(&meta, field.rename.is_some())?;
+                    field.rename = Some(get_lit_str(&name_value.lit)?.clone());
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("with") =>
+                {
+                    check_unset(&meta, field.with.is_some())?;
+                    field.with = Some(get_lit_str(&name_value.lit)?.parse()?);
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("skip_if") =>
+                {
+                    check_unset(&meta, field.skip_if.is_some())?;
+                    field.skip_if = Some(SkipIf::Path(get_lit_str(&name_value.lit)?.parse()?));
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip_if_default") => {
+                    check_unset(&meta, field.skip_if.is_some())?;
+                    field.skip_if = Some(SkipIf::Default);
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip_if_none") => {
+                    check_unset(&meta, field.skip_if.is_some())?;
+                    field.skip_if = Some(SkipIf::None);
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("flatten") => {
+                    check_unset(&meta, field.flatten)?;
+                    field.flatten = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("unordered") => {
+                    check_unset(&meta, field.unordered)?;
+                    field.unordered = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("self_hash") => {
+                    check_unset(&meta, field.self_hash)?;
+                    field.self_hash = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("profile") =>
+                {
+                    let profile = get_lit_str(&name_value.lit)?;
+                    check_unset(
+                        &meta,
+                        field.profiles.iter().any(|p| p.value() == profile.value()),
+                    )?;
+                    field.profiles.push(profile.clone());
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("normalize") =>
+                {
+                    let normalize = get_lit_str(&name_value.lit)?;
+                    let variant = match normalize.value().as_str() {
+                        "nfc" => "Nfc",
+                        "lowercase" => "Lowercase",
+                        "trim" => "Trim",
+                        _ => {
+                            return Err(Error::new_spanned(
+                                normalize,
+                                "unknown normalization, expected `nfc`, `lowercase` or `trim`",
+                            ))
+                        }
+                    };
+                    field.normalize.push(Ident::new(variant, normalize.span()));
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("canonical_float") => {
+                    check_unset(&meta, field.quantize.is_some())?;
+                    field.quantize = Some(Quantize::Canonical);
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("round") =>
+                {
+                    check_unset(&meta, field.quantize.is_some())?;
+                    let lit = get_lit_int(&name_value.lit)?;
+                    let places = lit.base10_parse()?;
+                    if places > MAX_DIGITS {
+                        return Err(Error::new_spanned(
+                            lit,
+                            format!("expected at most {MAX_DIGITS} decimal places"),
+                        ));
+                    }
+                    field.quantize = Some(Quantize::Round(places));
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("significant_digits") =>
+                {
+                    check_unset(&meta, field.quantize.is_some())?;
+                    let lit = get_lit_int(&name_value.lit)?;
+                    let digits = lit.base10_parse()?;
+                    if digits == 0 || digits > MAX_DIGITS {
+                        return Err(Error::new_spanned(
+                            lit,
+                            format!("expected between 1 and {MAX_DIGITS} significant digits"),
+                        ));
+                    }
+                    field.quantize = Some(Quantize::SignificantDigits(digits));
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("truncate") =>
+                {
+                    check_unset(&meta, field.truncate.is_some())?;
+                    let truncate = get_lit_str(&name_value.lit)?;
+                    let variant = match truncate.value().as_str() {
+                        "s" => "Seconds",
+                        "ms" => "Millis",
+                        "us" => "Micros",
+                        "ns" => "Nanos",
+                        _ => {
+                            return Err(Error::new_spanned(
+                                truncate,
+                                "unknown precision, expected `s`, `ms`, `us` or `ns`",
+                            ))
+                        }
+                    };
+                    field.truncate = Some(Ident::new(variant, truncate.span()));
+                }
+                _ => return Err(unknown(&meta, "field")),
+            }
+            // each of these decides how the field is hashed
+            check_exclusive(
+                &meta,
+                &[
+                    ("skip", field.skip),
+                    ("json", field.json),
+                    ("with", field.with.is_some()),
+                    ("flatten", field.flatten),
+                    ("unordered", field.unordered),
+                    ("self_hash", field.self_hash),
+                    ("normalize", !field.normalize.is_empty()),
+                    (
+                        "canonical_float",
+                        matches!(field.quantize, Some(Quantize::Canonical)),
+                    ),
+                    ("round", matches!(field.quantize, Some(Quantize::Round(_)))),
+                    (
+                        "significant_digits",
+                        matches!(field.quantize, Some(Quantize::SignificantDigits(_))),
+                    ),
+                    ("truncate", field.truncate.is_some()),
+                ],
+            )?;
+            check_exclusive(
+                &meta,
+                &[
+                    ("self_hash", field.self_hash),
+                    ("skip_if", field.skip_if.is_some()),
+                ],
+            )?;
+            check_exclusive(
+                &meta,
+                &[
+                    ("self_hash", field.self_hash),
+                    ("profile", !field.profiles.is_empty()),
+                ],
+            )?;
+            check_exclusive(
+                &meta,
+                &[
+                    ("flatten", field.flatten),
+                    ("rename", field.rename.is_some()),
+                ],
+            )?;
+            check_exclusive(
+                &meta,
+                &[("skip", field.skip), ("skip_if", field.skip_if.is_some())],
+            )?;
+        }
+
+        if field.self_hash {
+            if !util::is_digest_type(&syn_field.ty) {
+                return Err(Error::new_spanned(
+                    &syn_field.ty,
+                    "#[niz(self_hash)] requires a field of type `[u8; 32]`",
+                ));
+            }
+            field.skip = true;
+        } else if container.serde_compat {
+            field.apply_serde(syn_field, rename_all)?;
+        }
+
+        Ok(field)
+    }
+
+    /// Fills in the options that `#[niz(...)]` left unset from the field's
+    /// `#[serde(...)]` attributes. Explicit niz options always take priority.
+    fn apply_serde(
+        &mut self,
+        syn_field: &syn::Field,
+        rename_all: Option<RenameRule>,
+    ) -> Result<()> {
+        let has_encoding = self.json
+            || self.with.is_some()
+            || self.flatten
+            || self.unordered
+            || !self.normalize.is_empty()
+            || self.quantize.is_some()
+            || self.truncate.is_some();
+        for meta in serde_metas(&syn_field.attrs) {
+            let path = meta.path();
+            if path.is_ident("rename") {
+                if self.rename.is_none() && !self.flatten {
+                    self.rename = serde_name(&meta);
+                }
+            } else if path.is_ident("skip") || path.is_ident("skip_serializing") {
+                if matches!(meta, Meta::Path(_)) && !has_encoding && self.skip_if.is_none() {
+                    self.skip = true;
+                }
+            } else if path.is_ident("skip_serializing_if") {
+                if let Meta::NameValue(name_value) = &meta {
+                    if self.skip_if.is_none() && !self.skip {
+                        self.skip_if = Some(SkipIf::Path(get_lit_str(&name_value.lit)?.parse()?));
+                    }
+                }
+            } else if path.is_ident("flatten")
+                && matches!(meta, Meta::Path(_))
+                && !has_encoding
+                && !self.skip
+                && self.rename.is_none()
+            {
+                if util::is_map_type(&syn_field.ty) {
+                    return Err(Error::new_spanned(
+                        &meta,
+                        "#[serde(flatten)] on a map has no niz equivalent; give the field an \
+                         explicit #[niz(...)] option, e.g. #[niz(rename = \"...\")] to hash it \
+                         as a regular field or #[niz(skip)]",
+                    ));
+                }
+                self.flatten = true;
+            }
+        }
+        if let (None, Some(rule), Some(ident)) = (&self.rename, rename_all, &syn_field.ident) {
+            if !self.flatten {
+                let name = rule.apply_to_field(&ident.unraw().to_string());
+                self.rename = Some(LitStr::new(&name, ident.span()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Flattens every `#[serde(...)]` in `attrs` into its list of options. Options
+/// that do not parse are left for serde itself to report.
+fn serde_metas(attrs: &[Attribute]) -> Vec<Meta> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("serde"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|nested| match nested {
+            NestedMeta::Meta(meta) => Some(meta),
+            NestedMeta::Lit(_) => None,
+        })
+        .collect()
+}
+
+/// The serialized name in a serde `name = "..."` or
+/// `name(serialize = "...")` option.
+fn serde_name(meta: &Meta) -> Option<LitStr> {
+    match meta {
+        Meta::NameValue(name_value) => match &name_value.lit {
+            Lit::Str(lit) => Some(lit.clone()),
+            _ => None,
+        },
+        Meta::List(list) => list.nested.iter().find_map(|nested| match nested {
+            NestedMeta::Meta(Meta::NameValue(name_value))
+                if name_value.path.is_ident("serialize") =>
+            {
+                match &name_value.lit {
+                    Lit::Str(lit) => Some(lit.clone()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }),
+        Meta::Path(_) => None,
+    }
+}
+
+/// The rule in a serde `rename_all = "..."` option, or its
+/// `rename_all(serialize = "...")` form.
+fn serde_rename_rule(meta: &Meta) -> Result<Option<RenameRule>> {
+    match serde_name(meta) {
+        Some(rule) => RenameRule::from_str(&rule.value())
+            .map(Some)
+            .ok_or_else(|| Error::new_spanned(&rule, "unknown rename_all rule")),
+        None => Ok(None),
+    }
+}
+
+/// Flattens every `#[niz(a, b = "c")]` in `attrs` into its list of options,
+/// ignoring attributes that do not belong to niz.
+fn niz_metas(attrs: &[Attribute]) -> Result<Vec<NestedMeta>> {
+    let mut metas = Vec::new();
+    for attr in attrs {
+        if !attr.path.is_ident("niz") {
+            // ignore non-niz attributes
+            continue;
+        }
+        match attr.parse_meta()? {
+            Meta::List(list) => metas.extend(list.nested),
+            _ => {
+                return Err(Error::new_spanned(
+                    attr,
+                    "expected a list of options, as in `#[niz(...)]`",
+                ))
+            }
+        }
+    }
+    Ok(metas)
+}
+
+fn get_lit_str(lit: &Lit) -> Result<&LitStr> {
+    match lit {
+        Lit::Str(lit) => Ok(lit),
+        lit => Err(Error::new_spanned(lit, "expected a string literal")),
+    }
+}
+
+fn get_lit_int(lit: &Lit) -> Result<&LitInt> {
+    match lit {
+        Lit::Int(lit) => Ok(lit),
+        lit => Err(Error::new_spanned(lit, "expected an integer literal")),
+    }
+}
+
+fn check_unset(meta: &NestedMeta, is_set: bool) -> Result<()> {
+    if is_set {
+        return Err(Error::new_spanned(meta, "duplicate niz attribute"));
+    }
+    Ok(())
+}
+
+/// Fails if more than one of the mutually exclusive `options` is set.
+fn check_exclusive(meta: &NestedMeta, options: &[(&str, bool)]) -> Result<()> {
+    let set: Vec<_> = options
+        .iter()
+        .filter_map(|(name, is_set)| is_set.then_some(name))
+        .collect();
+    if set.len() > 1 {
+        return Err(Error::new_spanned(
+            meta,
+            format!("`{}` and `{}` cannot be used together", set[0], set[1]),
+        ));
+    }
+    Ok(())
+}
+
+fn unknown(meta: &NestedMeta, position: &str) -> Error {
+    let name = match meta {
+        NestedMeta::Meta(meta) => meta.path().get_ident().map(ToString::to_string),
+        NestedMeta::Lit(_) => None,
+    };
+    match name {
+        Some(name) => {
+            Error::new_spanned(meta, format!("unknown niz {position} attribute `{name}`"))
+        }
+        None => Error::new_spanned(meta, format!("unknown niz {position} attribute")),
+    }
+}
@@ -0,0 +1,84 @@
+//! Case conversions for `#[serde(rename_all = "...")]`, matching serde's own
+//! rules so that hash labels agree with serialized names.
+
+use self::RenameRule::*;
+
+#[derive(Clone, Copy)]
+pub(crate) enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    pub fn from_str(rule: &str) -> Option<Self> {
+        Some(match rule {
+            "lowercase" => Lower,
+            "UPPERCASE" => Upper,
+            "PascalCase" => Pascal,
+            "camelCase" => Camel,
+            "snake_case" => Snake,
+            "SCREAMING_SNAKE_CASE" => ScreamingSnake,
+            "kebab-case" => Kebab,
+            "SCREAMING-KEBAB-CASE" => ScreamingKebab,
+            _ => return None,
+        })
+    }
+
+    /// Applies the rule to a variant name, which is assumed to be PascalCase.
+    pub fn apply_to_variant(self, variant: &str) -> String {
+        match self {
+            Pascal => variant.to_owned(),
+            Lower => variant.to_ascii_lowercase(),
+            Upper => variant.to_ascii_uppercase(),
+            Camel => variant[..1].to_ascii_lowercase() + &variant[1..],
+            Snake => {
+                let mut snake = String::new();
+                for (i, ch) in variant.char_indices() {
+                    if i > 0 && ch.is_uppercase() {
+                        snake.push('_');
+                    }
+                    snake.push(ch.to_ascii_lowercase());
+                }
+                snake
+            }
+            ScreamingSnake => Snake.apply_to_variant(variant).to_ascii_uppercase(),
+            Kebab => Snake.apply_to_variant(variant).replace('_', "-"),
+            ScreamingKebab => ScreamingSnake.apply_to_variant(variant).replace('_', "-"),
+        }
+    }
+
+    /// Applies the rule to a field name, which is assumed to be snake_case.
+    pub fn apply_to_field(self, field: &str) -> String {
+        match self {
+            Lower | Snake => field.to_owned(),
+            Upper | ScreamingSnake => field.to_ascii_uppercase(),
+            Pascal => {
+                let mut pascal = String::new();
+                let mut capitalize = true;
+                for ch in field.chars() {
+                    if ch == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        pascal.push(ch.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        pascal.push(ch);
+                    }
+                }
+                pascal
+            }
+            Camel => {
+                let pascal = Pascal.apply_to_field(field);
+                pascal[..1].to_ascii_lowercase() + &pascal[1..]
+            }
+            Kebab => field.replace('_', "-"),
+            ScreamingKebab => ScreamingSnake.apply_to_field(field).replace('_', "-"),
+        }
+    }
+}
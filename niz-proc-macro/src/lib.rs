@@ -1,136 +1,1039 @@
-use core::panic;
-
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{Data, DataEnum, DataStruct, DeriveInput, Fields, Ident};
+use proc_macro2::{Literal, TokenStream as TokenStream2};
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    parse_macro_input, parse_quote, Data, DataEnum, DataStruct, DeriveInput, Error, Expr, Field,
+    Fields, Generics, Index, Member, Path, Result, WherePredicate,
+};
 
+mod attr;
+mod case;
 mod util;
 
+/// Derives `niz::hash::Hashable`.
+///
+/// A struct hashes to `sha3(prefix(Type) || field_0 || field_1 || ...)`, where
+/// each `field_i` is `sha3(prefix(label) || value.hash())` and fields appear in
+/// declaration order. Named fields are labelled by their name and tuple fields
+/// by their position, so `struct UserId(Uuid)` hashes its only field under
+/// `"0"`. A unit struct has no fields and hashes to `sha3(prefix(Type))`.
+///
+/// The derive implements `Hashable::hash_into`, which describes the value to
+/// any `niz::hash::NizHasher`. The encodings described here are those of
+/// `hash`, which builds one digest per node; `hash_stream` frames the same
+/// tree into a single digest instead. `hash_with::<D>()` builds the same
+/// nodes with any `niz::digest::Digest` in place of SHA3-256, except that a
+/// `#[niz(with = "...")]` function that returns a digest still feeds it as is.
+///
+/// `#[niz(name = "...")]` on the type and `#[niz(rename = "...")]` on a field
+/// replace the label it is hashed under, so renaming the Rust item does not
+/// change its hash.
+///
+/// `#[niz(with = "path")]` on a field hashes it with `path(&value)`, for any
+/// `fn(&T) -> [u8; 32]`, in place of `value.hash()`. `path` can also be any
+/// `fn(&T, &mut H)` that feeds the value into a `NizHasher` like
+/// `Hashable::hash_into` does.
+///
+/// `#[niz(unordered)]` on a sequence field hashes it with
+/// `niz::adt::hash_unordered`, so the same items in any order give the same
+/// hash.
+///
+/// `#[niz(normalize = "nfc")]`, `"lowercase"` or `"trim"` on a string field
+/// hashes it as the string it normalizes to, with
+/// `niz::scalar::hash_normalized`. Repeated, the normalizations apply in order.
+///
+/// `#[niz(round = N)]` and `#[niz(significant_digits = N)]` on an `f32` or
+/// `f64` field round it to `N` decimal places or significant digits before it
+/// is hashed, with `niz::scalar::hash_quantized`, so results that differ only
+/// in their last bits hash the same. They also hash `-0.0` as `0.0` and every
+/// NaN alike, which `#[niz(canonical_float)]` does on its own.
+///
+/// `#[niz(truncate = "s")]`, `"ms"`, `"us"` or `"ns"` on a `DateTime<Utc>`
+/// field truncates it to that precision before it is hashed, with
+/// `niz::scalar::hash_truncated`, which also formats it with a fixed number of
+/// fractional digits.
+///
+/// `#[niz(skip_if = "path")]` leaves a field out of the hash whenever
+/// `path(&value)` returns `true`, and `#[niz(skip_if_default)]` and
+/// `#[niz(skip_if_none)]` do the same when it equals `Default::default()` or is
+/// `None`. A field added this way does not change the hash of values that
+/// leave it neutral.
+///
+/// `#[niz(flatten)]` on a field of another derived struct type feeds that
+/// struct's field digests straight into the parent, so the result is the same
+/// as if they had been declared inline.
+///
+/// `#[niz(profile = "name")]` on struct fields, repeated for each profile a
+/// field belongs to, also implements `niz::hash::HashableProfiles`.
+/// `value.hash_profile("name")` then hashes only the fields in that profile,
+/// skipped or not, to `sha3(prefix(Type) || prefix(name) || field_0 || ...)`,
+/// and `hash_profile_with::<D>("name")` does the same over any digest.
+///
+/// `#[niz(self_hash)]` on a `[u8; 32]` struct field leaves it out of the hash
+/// and also implements `niz::hash::SelfHashable`, whose `seal` stores the
+/// struct's hash in the field and `verify_self_hash` checks it. `seal_with`
+/// and `verify_self_hash_with` do the same with `hash_with`.
+///
+/// `#[niz(remote = "path::Type")]` on a mirror of a type from another crate,
+/// declared with the same fields or variants, implements no trait. Instead it
+/// gives the mirror an associated `fn hash(value: &path::Type) -> [u8; 32]`
+/// and the `hash_into` it is built on. Passed to
+/// `#[niz(with = "Mirror::hash_into")]`, the latter hashes the foreign type
+/// exactly as if it had derived `Hashable` itself, with any digest or hasher.
+///
+/// Every derived type also implements `niz::schema::HashSchema`, which
+/// describes the labels, order, skip conditions and encoding of its fields,
+/// links to the schemas of field types that have one, and can be exported as
+/// JSON with `Schema::to_json`.
+///
+/// `#[niz(transparent)]` on a struct with a single hashed field makes it hash
+/// exactly like that field, with no type or field label, so wrapping a value in
+/// a newtype does not change its hash.
+///
+/// `#[niz(serde_compat)]` on the type also reads its `#[serde(...)]`
+/// attributes: `rename` and `rename_all` change labels the same way they change
+/// serialized names, `skip`, `skip_serializing` and `skip_serializing_if` leave
+/// fields out, and `flatten` flattens. Explicit niz options take priority.
+/// `flatten` on a map field is an error, since a map has no fields to flatten;
+/// such a field needs its own `#[niz(...)]` option.
+///
+/// `#[niz(crate = "path")]` makes the generated code reach niz through `path`
+/// in place of `::niz`, for crates that re-export it. `#[niz(json)]` goes
+/// through niz's own `serde_json` re-export, so it does not need a direct
+/// dependency either.
+///
+/// `#[niz(sorted_fields)]` on the type hashes fields in the byte order of their
+/// labels rather than in declaration order, so reordering fields in the source
+/// does not change the hash.
+///
+/// An enum hashes to `sha3(prefix(Type) || variant)`, where `variant` is
+/// `sha3(prefix(Variant) || discriminant.hash() || field_0 || ...)` and the
+/// variant fields are encoded the same way as struct fields. The discriminant
+/// is the value rustc assigns to the variant, whether written out or implicit,
+/// as the integer type of the enum's `#[repr(...)]`, with `isize` and `usize`
+/// widened to `i64` and `u64` so the hash is the same on every target. Enums
+/// without one hash it as `u8`, and fail to compile if a discriminant is
+/// outside `0..=255`.
+///
+/// `#[niz(rename = "...")]` and `#[niz(index = N)]` on a variant pin its label
+/// and discriminant, so that renaming, inserting or reordering variants does
+/// not change the hashes of the others.
 #[proc_macro_derive(Hashable, attributes(niz))]
 pub fn derive_hashable(input: TokenStream) -> TokenStream {
-    let ast: DeriveInput = syn::parse(input).unwrap();
+    let ast = parse_macro_input!(input as DeriveInput);
+
+    let expanded = match &ast.data {
+        Data::Struct(data) => expand_derive_hashable_for_struct(&ast, data),
+        Data::Enum(data) => expand_derive_hashable_for_enum(&ast, data),
+        Data::Union(data) => Err(Error::new_spanned(
+            data.union_token,
+            "hashable can only be derived for structs and enums",
+        )),
+    };
+
+    TokenStream::from(expanded.unwrap_or_else(Error::into_compile_error))
+}
+
+/// Parses the `#[niz(...)]` options of every field in `fields`, which serde
+/// would rename by `rename_all`.
+fn parse_fields<'a>(
+    fields: impl IntoIterator<Item = &'a Field>,
+    container: &attr::Container,
+    rename_all: Option<case::RenameRule>,
+) -> Result<Vec<(&'a Field, attr::Field)>> {
+    fields
+        .into_iter()
+        .map(|field| {
+            Ok((
+                field,
+                attr::Field::from_field(field, container, rename_all)?,
+            ))
+        })
+        .collect()
+}
+
+fn expand_derive_hashable_for_struct(ast: &DeriveInput, data: &DataStruct) -> Result<TokenStream2> {
     let ident = &ast.ident;
+    let container = attr::Container::from_attrs(&ast.attrs)?;
+    let fields = parse_fields(&data.fields, &container, container.rename_all)?;
+    let generics = add_trait_bounds(ast, &container, &fields);
+    if container.transparent {
+        return expand_derive_hashable_for_transparent(ast, &container, &generics, &fields);
+    }
 
-    match &ast.data {
-        Data::Struct(data) => expand_derive_hashable_for_struct(ident, data),
-        Data::Enum(data) => expand_derive_hashable_for_enum(ident, data),
-        _ => panic!("hashable can only be derived for structs and enums"),
+    let krate = container.krate();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let type_label = type_label(ast, &container);
+    let type_label_ref = expand_label(&krate, &type_label);
+
+    // every field is expanded, since skipped fields can still be in a profile
+    let mut hash_fields_impl = Vec::new();
+    for (i, (field, attrs)) in fields.iter().enumerate() {
+        let label = field_label(field, attrs, i);
+        let hash_field_impl = expand_hash_field(
+            &krate,
+            field,
+            attrs,
+            &label,
+            field_member(&container, field, i),
+        );
+        hash_fields_impl.push((attrs, label, hash_field_impl));
     }
-}
+    let select_fields = |include: &dyn Fn(&attr::Field) -> bool| {
+        let selected = hash_fields_impl
+            .iter()
+            .filter(|(attrs, _, _)| include(attrs))
+            .map(|(_, label, hash_field_impl)| (label.clone(), hash_field_impl.clone()))
+            .collect();
+        order_fields(&container, selected, &fields)
+    };
 
-fn expand_derive_hashable_for_struct(ident: &Ident, data: &DataStruct) -> TokenStream {
-    let hash_fields_impl = match &data.fields {
-        Fields::Named(named) => named.named.iter().filter_map(|field| {
-            if util::has_skip_attr(&field.attrs) {
-                return None;
+    let profiles = util::profile_names(fields.iter().map(|(_, attrs)| attrs));
+    let hash_profiles_impl = profiles
+        .iter()
+        .map(|profile| {
+            let hash_fields_impl =
+                select_fields(&|attrs| attrs.profiles.iter().any(|p| p.value() == *profile))?;
+            let profile_ref = expand_label(&krate, &quote! { #profile });
+            Ok(quote! {
+                #profile => {
+                    state.label(#profile_ref);
+                    #(#hash_fields_impl)*
+                }
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let hash_fields_impl = select_fields(&|attrs| !attrs.skip)?;
+
+    let fields_schema = fields
+        .iter()
+        .enumerate()
+        .map(|(i, (field, attrs))| {
+            let label = field_label(field, attrs, i);
+            let field_schema = expand_field_schema(&krate, field, attrs, &label);
+            (label, field_schema)
+        })
+        .collect();
+    let fields_schema = order_fields(&container, fields_schema, &fields)?;
+    let schema_impl = expand_hash_schema(
+        ast,
+        &generics,
+        &krate,
+        quote! {
+            #krate::schema::Schema::Struct {
+                label: #type_label,
+                fields: ::std::vec![#(#fields_schema),*],
             }
+        },
+    );
 
-            let field_ident = field.ident.as_ref().unwrap();
+    if let Some(remote) = &container.remote {
+        if let Some((field, _)) = fields
+            .iter()
+            .find(|(_, attrs)| !attrs.profiles.is_empty() || attrs.self_hash)
+        {
+            return Err(Error::new_spanned(
+                field,
+                "`profile` and `self_hash` cannot be used in a #[niz(remote)] type",
+            ));
+        }
+        let remote_hash_impl = expand_remote_hash(
+            ast,
+            &generics,
+            &krate,
+            remote,
+            quote! {
+                state.begin();
+                state.label(#type_label_ref);
+                #(#hash_fields_impl)*
+                state.end();
+            },
+        );
+        return Ok(quote! {
+            #remote_hash_impl
 
-            Some(if util::has_json_attr(&field.attrs) {
-                quote! {
+            #schema_impl
+        });
+    }
+
+    let mut self_hash_fields = fields
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, attrs))| attrs.self_hash);
+    let self_hash_impl = match (self_hash_fields.next(), self_hash_fields.next()) {
+        (None, _) => None,
+        (Some((i, (field, _))), None) => {
+            let member = field_member(&container, field, i);
+            Some(quote! {
+                impl #impl_generics #krate::hash::SelfHashable for #ident #ty_generics #where_clause {
+                    fn seal_with<__D>(&mut self)
+                    where
+                        __D: #krate::digest::Digest,
                     {
-                        let mut field_output = [0u8; 32];
-                        let mut field_hasher = Sha3::v256();
-                        field_hasher.update(&::niz::hash::prefix(stringify!(#field_ident)));
-                        field_hasher.update(&::serde_json::to_value(&self.#field_ident).unwrap().hash());
-                        field_hasher.finalize(&mut field_output);
-                        hasher.update(&field_output);
+                        #member = #krate::hash::Hashable::hash_with::<__D>(self);
                     }
-                }
-            } else {
-                quote! {
+
+                    fn verify_self_hash_with<__D>(&self) -> bool
+                    where
+                        __D: #krate::digest::Digest,
                     {
-                        let mut field_output = [0u8; 32];
-                        let mut field_hasher = Sha3::v256();
-                        field_hasher.update(&::niz::hash::prefix(stringify!(#field_ident)));
-                        field_hasher.update(&self.#field_ident.hash());
-                        field_hasher.finalize(&mut field_output);
-                        hasher.update(&field_output);
+                        #member == #krate::hash::Hashable::hash_with::<__D>(self)
                     }
                 }
             })
-        }),
-        _ => panic!("hashable can only be derived for structs with named fields"),
+        }
+        (Some(_), Some((_, (field, _)))) => {
+            return Err(Error::new_spanned(
+                field,
+                "#[niz(self_hash)] can only be used on one field",
+            ))
+        }
     };
 
-    let expanded = quote! {
-        impl ::niz::hash::Hashable for #ident {
-            fn hash(&self) -> [u8; 32] {
-                use ::niz::tiny_keccak::{Hasher, Sha3};
+    let profiles_impl = (!profiles.is_empty()).then(|| {
+        quote! {
+            impl #impl_generics #krate::hash::HashableProfiles for #ident #ty_generics #where_clause {
+                fn hash_profile_with<__D>(&self, profile: &str) -> ::core::option::Option<[u8; 32]>
+                where
+                    __D: #krate::digest::Digest,
+                {
+                    use #krate::hash::NizHasher;
+
+                    let mut hasher = #krate::hash::NodeHasher::<__D>::default();
+                    let state = &mut hasher;
+                    state.begin();
+                    state.label(#type_label_ref);
+                    match profile {
+                        #(#hash_profiles_impl)*
+                        _ => return ::core::option::Option::None,
+                    }
+                    state.end();
+                    ::core::option::Option::Some(hasher.finalize())
+                }
+            }
+        }
+    });
 
-                let mut output = [0u8; 32];
-                let mut hasher = Sha3::v256();
-                hasher.update(&::niz::hash::prefix(stringify!(#ident)));
+    let expanded = quote! {
+        impl #impl_generics #krate::hash::HashableFields for #ident #ty_generics #where_clause {
+            fn hash_fields<__H>(&self, state: &mut __H)
+            where
+                __H: #krate::hash::NizHasher + ?Sized,
+            {
+                use #krate::hash::NizHasher;
 
                 #(#hash_fields_impl)*
+            }
+        }
 
-                hasher.finalize(&mut output);
-                output
+        impl #impl_generics #krate::hash::Hashable for #ident #ty_generics #where_clause {
+            fn hash_into<__H>(&self, state: &mut __H)
+            where
+                __H: #krate::hash::NizHasher + ?Sized,
+            {
+                use #krate::hash::NizHasher;
+
+                state.begin();
+                state.label(#type_label_ref);
+                #krate::hash::HashableFields::hash_fields(self, state);
+                state.end();
             }
         }
+
+        #profiles_impl
+
+        #self_hash_impl
+
+        #schema_impl
     };
 
-    TokenStream::from(expanded)
+    Ok(expanded)
 }
 
-fn expand_derive_hashable_for_enum(ident: &Ident, data: &DataEnum) -> TokenStream {
-    let is_enum = data
-        .variants
+/// Expands `#[niz(transparent)]`, which hashes a struct exactly like its only
+/// hashed field.
+fn expand_derive_hashable_for_transparent(
+    ast: &DeriveInput,
+    container: &attr::Container,
+    generics: &Generics,
+    fields: &[(&Field, attr::Field)],
+) -> Result<TokenStream2> {
+    let ident = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let mut hashed = fields
         .iter()
-        .all(|variant| matches!(variant.fields, Fields::Unit));
-    if !is_enum {
-        panic!("hashable can only be derived for enums that can be cast to `u8`");
-    }
-
-    let hash_variants_impl = data.variants.iter().map(|variant| {
-        let variant_ident = &variant.ident;
-        variant.discriminant.as_ref().map_or_else(
-            || {
-                quote! {
-                    Self::#variant_ident => {
-                        let mut variant_output = [0u8; 32];
-                        let mut variant_hasher = Sha3::v256();
-                        variant_hasher.update(&::niz::hash::prefix(stringify!(#variant_ident)));
-                        variant_hasher.update(&(*self as u8).hash());
-                        variant_hasher.finalize(&mut variant_output);
-                        hasher.update(&variant_output);
+        .enumerate()
+        .filter(|(_, (_, attrs))| !attrs.skip);
+    let (i, (field, attrs)) = match (hashed.next(), hashed.next()) {
+        (Some(field), None) => field,
+        _ => {
+            return Err(Error::new_spanned(
+                ident,
+                "#[niz(transparent)] requires a struct with exactly one hashed field",
+            ))
+        }
+    };
+    if attrs.flatten || attrs.skip_if.is_some() {
+        return Err(Error::new_spanned(
+            field,
+            "the field of a #[niz(transparent)] struct cannot use `flatten` or `skip_if`",
+        ));
+    }
+    if let Some((field, _)) = fields.iter().find(|(_, attrs)| !attrs.profiles.is_empty()) {
+        return Err(Error::new_spanned(
+            field,
+            "#[niz(profile)] cannot be used in a #[niz(transparent)] struct",
+        ));
+    }
+    if let Some((field, _)) = fields.iter().find(|(_, attrs)| attrs.self_hash) {
+        return Err(Error::new_spanned(
+            field,
+            "#[niz(self_hash)] cannot be used in a #[niz(transparent)] struct",
+        ));
+    }
+
+    let krate = container.krate();
+    let value_hash = expand_value_hash(&krate, attrs, &field_member(container, field, i));
+    let value_hash = quote! {
+        use #krate::hash::NizHasher;
+
+        #value_hash
+    };
+    let field_schema = expand_field_schema(&krate, field, attrs, &field_label(field, attrs, i));
+    let schema_impl = expand_hash_schema(
+        ast,
+        generics,
+        &krate,
+        quote! {
+            #krate::schema::Schema::Transparent {
+                field: #field_schema,
+            }
+        },
+    );
+
+    let hash_impl = match &container.remote {
+        Some(remote) => expand_remote_hash(ast, generics, &krate, remote, value_hash),
+        None => quote! {
+            impl #impl_generics #krate::hash::Hashable for #ident #ty_generics #where_clause {
+                fn hash_into<__H>(&self, state: &mut __H)
+                where
+                    __H: #krate::hash::NizHasher + ?Sized,
+                {
+                    #value_hash
+                }
+            }
+        },
+    };
+    let expanded = quote! {
+        #hash_impl
+
+        #schema_impl
+    };
+
+    Ok(expanded)
+}
+
+/// Expands the hashing functions of a `#[niz(remote = "...")]` type: an
+/// associated `hash(value: &Remote) -> [u8; 32]` that can be passed to
+/// `#[niz(with = "...")]`, and the `hash_into` it is built on. `body` feeds
+/// `value` into `state`.
+fn expand_remote_hash(
+    ast: &DeriveInput,
+    generics: &Generics,
+    krate: &Path,
+    remote: &Path,
+    body: TokenStream2,
+) -> TokenStream2 {
+    let ident = &ast.ident;
+    let vis = &ast.vis;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics #ident #ty_generics #where_clause {
+            #vis fn hash(value: &#remote #ty_generics) -> [u8; 32] {
+                let mut state = #krate::hash::NodeHasher::new();
+                Self::hash_into(value, &mut state);
+                state.finalize()
+            }
+
+            #vis fn hash_into<__H>(value: &#remote #ty_generics, state: &mut __H)
+            where
+                __H: #krate::hash::NizHasher + ?Sized,
+            {
+                use #krate::hash::NizHasher;
+
+                #body
+            }
+        }
+    }
+}
+
+/// Expands the `HashSchema` impl of a type, whose schema is built by `body`.
+fn expand_hash_schema(
+    ast: &DeriveInput,
+    generics: &Generics,
+    krate: &Path,
+    body: TokenStream2,
+) -> TokenStream2 {
+    let ident = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics #krate::schema::HashSchema for #ident #ty_generics #where_clause {
+            fn hash_schema() -> #krate::schema::Schema {
+                #body
+            }
+        }
+    }
+}
+
+/// Expands to the `FieldSchema` of a single field.
+fn expand_field_schema(
+    krate: &Path,
+    field: &Field,
+    attrs: &attr::Field,
+    label: &str,
+) -> TokenStream2 {
+    let ty = util::source_text(&field.ty);
+    let encoding = if let Some(with) = &attrs.with {
+        let with = util::source_text(with);
+        quote! { With(#with) }
+    } else if attrs.json {
+        quote! { Json }
+    } else if attrs.unordered {
+        quote! { Unordered }
+    } else if attrs.flatten {
+        quote! { Flatten }
+    } else if let Some(quantize) = &attrs.quantize {
+        let quantization = expand_quantization(krate, quantize);
+        quote! { Quantized(#quantization) }
+    } else if let Some(truncate) = &attrs.truncate {
+        quote! { Truncated(#krate::scalar::TimePrecision::#truncate) }
+    } else if !attrs.normalize.is_empty() {
+        let normalize = &attrs.normalize;
+        quote! {
+            Normalized(::std::vec![#(#krate::scalar::Normalization::#normalize),*])
+        }
+    } else {
+        quote! { Hashable }
+    };
+    let skip = match &attrs.skip_if {
+        _ if attrs.skip => quote! { Always },
+        Some(attr::SkipIf::Path(path)) => {
+            let path = util::source_text(path);
+            quote! { If(#path) }
+        }
+        Some(attr::SkipIf::Default) => quote! { IfDefault },
+        Some(attr::SkipIf::None) => quote! { IfNone },
+        None => quote! { Never },
+    };
+    let profiles = &attrs.profiles;
+    let nested = if attrs.with.is_none()
+        && !attrs.json
+        && !attrs.unordered
+        && attrs.quantize.is_none()
+        && attrs.truncate.is_none()
+        && attrs.normalize.is_empty()
+    {
+        let field_ty = &field.ty;
+        quote! {
+            {
+                #[allow(unused_imports)]
+                use #krate::schema::{ViaHashSchema as _, ViaNoSchema as _};
+                (&#krate::schema::NestedSchema::<#field_ty>(::core::marker::PhantomData))
+                    .nested_schema()
+            }
+        }
+    } else {
+        quote! { ::core::option::Option::None }
+    };
+
+    quote! {
+        #krate::schema::FieldSchema {
+            label: #label,
+            ty: #ty,
+            nested: #nested,
+            encoding: #krate::schema::Encoding::#encoding,
+            skip: #krate::schema::Skip::#skip,
+            profiles: ::std::vec![#(#profiles),*],
+        }
+    }
+}
+
+/// Adds the bounds the generated impl needs to `ast`'s generics.
+///
+/// By default, every type parameter that appears in a hashed field is bound by
+/// `Hashable`, and the type of a `#[niz(flatten)]` field that mentions one is
+/// bound by `HashableFields`. A container-level `#[niz(bound = "...")]`
+/// replaces the inferred bounds with the given where-predicates.
+fn add_trait_bounds<'a, 'b: 'a>(
+    ast: &DeriveInput,
+    container: &attr::Container,
+    fields: impl IntoIterator<Item = &'a (&'b Field, attr::Field)>,
+) -> Generics {
+    let mut generics = ast.generics.clone();
+
+    let krate = container.krate();
+    let predicates = match &container.bound {
+        Some(bound) => bound.clone(),
+        None => {
+            let type_params: Vec<_> = generics.type_params().map(|param| &param.ident).collect();
+            let mut used = Vec::new();
+            let mut predicates: Vec<WherePredicate> = Vec::new();
+            for (field, attrs) in fields {
+                if (attrs.skip && attrs.profiles.is_empty()) || attrs.json || attrs.with.is_some() {
+                    continue;
+                }
+                if attrs.flatten {
+                    let mut flatten_used = Vec::new();
+                    let ty = &field.ty;
+                    util::collect_type_params(
+                        ty.to_token_stream(),
+                        &type_params,
+                        &mut flatten_used,
+                    );
+                    if !flatten_used.is_empty() {
+                        predicates.push(parse_quote! { #ty: #krate::hash::HashableFields });
                     }
+                    continue;
                 }
-            },
-            |(_, expr)| {
-                quote! {
-                    Self::#variant_ident => {
-                        let mut variant_output = [0u8; 32];
-                        let mut variant_hasher = Sha3::v256();
-                        variant_hasher.update(&::niz::hash::prefix(stringify!(#variant_ident)));
-                        variant_hasher.update(&(#expr).hash());
-                        variant_hasher.finalize(&mut variant_output);
-                        hasher.update(&variant_output);
+                util::collect_type_params(field.ty.to_token_stream(), &type_params, &mut used);
+            }
+            type_params
+                .into_iter()
+                .filter(|param| used.contains(param))
+                .map(|param| parse_quote! { #param: #krate::hash::Hashable })
+                .chain(predicates)
+                .collect()
+        }
+    };
+
+    generics.make_where_clause().predicates.extend(predicates);
+    generics
+}
+
+/// The label a type is hashed under: its `#[niz(name = "...")]`, or else its
+/// Rust name. A remote type is labelled by the name of the type it mirrors.
+fn type_label(ast: &DeriveInput, container: &attr::Container) -> TokenStream2 {
+    let ident = match &container.remote {
+        Some(remote) => &remote.segments.last().unwrap().ident,
+        None => &ast.ident,
+    };
+    match &container.name {
+        Some(name) => quote! { #name },
+        None => quote! { stringify!(#ident) },
+    }
+}
+
+/// Expands to a `&'static Label` for `label`, a string expression, so that its
+/// prefix is computed at compile time.
+fn expand_label(krate: &Path, label: &TokenStream2) -> TokenStream2 {
+    quote! {
+        {
+            const LABEL: #krate::hash::Label = #krate::hash::Label::new(#label);
+            &LABEL
+        }
+    }
+}
+
+/// The label a field is hashed under: its `#[niz(rename = "...")]`, or else
+/// its name for named fields, and its position (`"0"`, `"1"`, ...) for tuple
+/// fields.
+fn field_label(field: &Field, attrs: &attr::Field, index: usize) -> String {
+    match (&attrs.rename, &field.ident) {
+        (Some(rename), _) => rename.value(),
+        (None, Some(field_ident)) => field_ident.to_string(),
+        (None, None) => index.to_string(),
+    }
+}
+
+/// Puts the expanded fields in the order they are hashed in: declaration
+/// order, or label order with `#[niz(sorted_fields)]`.
+fn order_fields(
+    container: &attr::Container,
+    mut hash_fields_impl: Vec<(String, TokenStream2)>,
+    fields: &[(&Field, attr::Field)],
+) -> Result<Vec<TokenStream2>> {
+    if container.sorted_fields {
+        if let Some((field, _)) = fields.iter().find(|(_, attrs)| attrs.flatten) {
+            return Err(Error::new_spanned(
+                field,
+                "#[niz(flatten)] cannot be used in a #[niz(sorted_fields)] type",
+            ));
+        }
+        // fields with the same label would have no order between them
+        let mut labels = Vec::new();
+        for (i, (field, attrs)) in fields.iter().enumerate() {
+            let label = field_label(field, attrs, i);
+            if labels.contains(&label) {
+                return Err(Error::new_spanned(
+                    field,
+                    format!("duplicate field label `{label}` in a #[niz(sorted_fields)] type"),
+                ));
+            }
+            labels.push(label);
+        }
+        hash_fields_impl.sort_by(|(a, _), (b, _)| a.cmp(b));
+    }
+    Ok(hash_fields_impl
+        .into_iter()
+        .map(|(_, hash_field_impl)| hash_field_impl)
+        .collect())
+}
+
+/// The expression `self.field` or `self.0` that accesses a struct field.
+fn field_member(container: &attr::Container, field: &Field, index: usize) -> TokenStream2 {
+    let receiver = container.receiver();
+    let member = match &field.ident {
+        Some(field_ident) => Member::Named(field_ident.clone()),
+        None => Member::Unnamed(Index::from(index)),
+    };
+    quote! { #receiver.#member }
+}
+
+/// Expands to a statement that feeds a single field value, without its
+/// label, into `state` as one node.
+fn expand_value_hash(krate: &Path, attrs: &attr::Field, value: &TokenStream2) -> TokenStream2 {
+    if let Some(with) = &attrs.with {
+        quote! { #krate::hash::HashFn::hash_value(&#with, &#value, state); }
+    } else if attrs.json {
+        quote! {
+            #krate::hash::Hashable::hash_into(
+                &#krate::serde_json::to_value(&#value).unwrap(),
+                state,
+            );
+        }
+    } else if attrs.unordered {
+        quote! { #krate::adt::hash_unordered_into(&#value, state); }
+    } else if let Some(quantize) = &attrs.quantize {
+        let quantization = expand_quantization(krate, quantize);
+        quote! {
+            #krate::hash::Hashable::hash_into(
+                &#krate::scalar::Quantization::apply(#quantization, #value),
+                state,
+            );
+        }
+    } else if let Some(truncate) = &attrs.truncate {
+        quote! {
+            #krate::scalar::hash_truncated_into(
+                &#value,
+                #krate::scalar::TimePrecision::#truncate,
+                state,
+            );
+        }
+    } else if !attrs.normalize.is_empty() {
+        let normalize = &attrs.normalize;
+        quote! {
+            #krate::hash::Hashable::hash_into(
+                &#krate::scalar::normalize(
+                    &#value,
+                    &[#(#krate::scalar::Normalization::#normalize),*],
+                ),
+                state,
+            );
+        }
+    } else {
+        quote! { #krate::hash::Hashable::hash_into(&#value, state); }
+    }
+}
+
+/// Expands to the `niz::scalar::Quantization` of a float field.
+fn expand_quantization(krate: &Path, quantize: &attr::Quantize) -> TokenStream2 {
+    match quantize {
+        attr::Quantize::Canonical => quote! { #krate::scalar::Quantization::Canonical },
+        attr::Quantize::Round(places) => quote! { #krate::scalar::Quantization::Round(#places) },
+        attr::Quantize::SignificantDigits(digits) => {
+            quote! { #krate::scalar::Quantization::SignificantDigits(#digits) }
+        }
+    }
+}
+
+/// Expands to a block that feeds a single field into `state`, which must be
+/// a `&mut impl NizHasher`, as a node holding its label and its value.
+fn expand_hash_field(
+    krate: &Path,
+    field: &Field,
+    attrs: &attr::Field,
+    label: &str,
+    value: TokenStream2,
+) -> TokenStream2 {
+    let value_hash = expand_value_hash(krate, attrs, &value);
+    let label_ref = expand_label(krate, &quote! { #label });
+
+    let hash_field = if attrs.flatten {
+        quote! {
+            {
+                #krate::hash::HashableFields::hash_fields(&#value, state);
+            }
+        }
+    } else {
+        quote! {
+            {
+                state.begin();
+                state.label(#label_ref);
+                #value_hash
+                state.end();
+            }
+        }
+    };
+
+    let ty = &field.ty;
+    match &attrs.skip_if {
+        Some(attr::SkipIf::Path(path)) => quote! {
+            if !#path(&#value) #hash_field
+        },
+        Some(attr::SkipIf::Default) => quote! {
+            if #value != <#ty as ::core::default::Default>::default() #hash_field
+        },
+        Some(attr::SkipIf::None) => quote! {
+            if ::core::option::Option::is_some(&#value) #hash_field
+        },
+        None => hash_field,
+    }
+}
+
+/// Fails on the second of two variants with the same label or the same
+/// `#[niz(index = N)]`, which would hash alike.
+fn check_unique_variants<'a>(
+    variants: impl IntoIterator<Item = (&'a syn::Variant, &'a attr::Variant)>,
+) -> Result<()> {
+    let mut labels = Vec::new();
+    let mut indices = Vec::new();
+    for (variant, attrs) in variants {
+        let (label, span) = match &attrs.rename {
+            Some(rename) => (rename.value(), rename.to_token_stream()),
+            None => (variant.ident.to_string(), variant.ident.to_token_stream()),
+        };
+        if labels.contains(&label) {
+            return Err(Error::new_spanned(
+                span,
+                format!("duplicate variant label `{label}`"),
+            ));
+        }
+        labels.push(label);
+
+        if let Some(index) = &attrs.index {
+            let value = index.base10_digits().to_owned();
+            if indices.contains(&value) {
+                return Err(Error::new_spanned(
+                    index,
+                    format!("duplicate variant index `{value}`"),
+                ));
+            }
+            indices.push(value);
+        }
+    }
+    Ok(())
+}
+
+fn expand_derive_hashable_for_enum(ast: &DeriveInput, data: &DataEnum) -> Result<TokenStream2> {
+    let ident = &ast.ident;
+    let container = attr::Container::from_attrs(&ast.attrs)?;
+    if container.transparent {
+        return Err(Error::new_spanned(
+            ident,
+            "#[niz(transparent)] can only be used on structs",
+        ));
+    }
+    let variants = data
+        .variants
+        .iter()
+        .map(|variant| {
+            let variant_attrs = attr::Variant::from_variant(variant, &container)?;
+            // serde's container `rename_all` renames the variants of an enum,
+            // not their fields
+            let rename_all = variant_attrs.rename_all.or(container.rename_all_fields);
+            let fields = parse_fields(&variant.fields, &container, rename_all)?;
+            Ok((variant, variant_attrs, fields))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    check_unique_variants(variants.iter().map(|(variant, attrs, _)| (*variant, attrs)))?;
+    let generics = add_trait_bounds(
+        ast,
+        &container,
+        variants.iter().flat_map(|(_, _, fields)| fields),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let type_label = type_label(ast, &container);
+
+    let krate = container.krate();
+    let type_label_ref = expand_label(&krate, &type_label);
+    let receiver = container.receiver();
+    let variant_prefix = match &container.remote {
+        Some(remote) => quote! { #remote },
+        None => quote! { Self },
+    };
+    // Discriminants are hashed as the type of the `#[repr(...)]` alone, so the
+    // implicit and written-out forms of the same value hash alike. Without
+    // one, rustc types them as `isize` but they are hashed as `u8`, and each
+    // is checked at compile time to fit.
+    let (repr, hash_repr, check_fits) = match util::repr_type(&ast.attrs) {
+        Some(repr) => {
+            let hash_repr = util::portable_repr_type(&repr);
+            (repr, hash_repr, quote! {})
+        }
+        None => {
+            let check_fits = quote! {
+                const _: () = ::std::assert!(
+                    0 <= DISCRIMINANT && DISCRIMINANT <= 255,
+                    "an enum without a #[repr(...)] hashes its discriminants as u8, so they \
+                     must be in 0..=255; add a #[repr(...)] to hash them as a wider type",
+                );
+            };
+            (format_ident!("isize"), format_ident!("u8"), check_fits)
+        }
+    };
+
+    // Implicit discriminants count up from the most recent explicit one, the
+    // same way rustc assigns them.
+    let mut last_discriminant: Option<&Expr> = None;
+    let mut next_offset = 0usize;
+
+    let (hash_variants_impl, variants_schema): (Vec<_>, Vec<_>) = variants
+        .iter()
+        .map(|(variant, variant_attrs, fields)| {
+            let variant_ident = &variant.ident;
+            let variant_label = match &variant_attrs.rename {
+                Some(rename) => quote! { #rename },
+                None => quote! { stringify!(#variant_ident) },
+            };
+            let variant_label_ref = expand_label(&krate, &variant_label);
+
+            let discriminant = match &variant.discriminant {
+                Some((_, expr)) => {
+                    last_discriminant = Some(expr);
+                    next_offset = 1;
+                    quote! { #expr }
+                }
+                None => {
+                    let offset = Literal::usize_unsuffixed(next_offset);
+                    next_offset += 1;
+                    match last_discriminant {
+                        Some(expr) => quote! { (#expr) + #offset },
+                        None => quote! { #offset },
                     }
                 }
-            },
-        )
-    });
+            };
+            // `#[niz(index = N)]` replaces the discriminant in the hash only, so
+            // rustc's numbering of the following variants is unaffected
+            let discriminant = match &variant_attrs.index {
+                Some(index) => quote! { #index },
+                None => discriminant,
+            };
 
-    let expanded = quote! {
-        impl ::niz::hash::Hashable for #ident {
-            fn hash(&self) -> [u8; 32] {
-                use ::niz::tiny_keccak::{Hasher, Sha3};
+            let mut bindings = Vec::new();
+            let mut hash_fields_impl = Vec::new();
+            let mut fields_schema = Vec::new();
+            for (i, (field, attrs)) in fields.iter().enumerate() {
+                let label = field_label(field, attrs, i);
+                fields_schema.push((
+                    label.clone(),
+                    expand_field_schema(&krate, field, attrs, &label),
+                ));
+                if let Some(profile) = attrs.profiles.first() {
+                    return Err(Error::new_spanned(
+                        profile,
+                        "#[niz(profile)] can only be used on struct fields",
+                    ));
+                }
+                if attrs.self_hash {
+                    return Err(Error::new_spanned(
+                        field,
+                        "#[niz(self_hash)] can only be used on struct fields",
+                    ));
+                }
+                if attrs.skip {
+                    bindings.push(quote! { _ });
+                    continue;
+                }
 
-                let mut output = [0u8; 32];
-                let mut hasher = Sha3::v256();
-                hasher.update(&::niz::hash::prefix(stringify!(#ident)));
+                let binding = format_ident!("__field{}", i);
+                hash_fields_impl.push((
+                    label.clone(),
+                    expand_hash_field(&krate, field, attrs, &label, quote! { (*#binding) }),
+                ));
+                bindings.push(quote! { #binding });
+            }
+            let hash_fields_impl = order_fields(&container, hash_fields_impl, fields)?;
+            let fields_schema = order_fields(&container, fields_schema, fields)?;
 
-                match self {
-                #(#hash_variants_impl)*
+            let pattern = match &variant.fields {
+                Fields::Named(named) => {
+                    let field_idents = named.named.iter().map(|field| &field.ident);
+                    quote! { { #(#field_idents: #bindings),* } }
                 }
+                Fields::Unnamed(_) => quote! { ( #(#bindings),* ) },
+                Fields::Unit => quote! {},
+            };
 
-                hasher.finalize(&mut output);
-                output
+            let hash_variant_impl = quote! {
+                #variant_prefix::#variant_ident #pattern => {
+                    state.begin();
+                    state.label(#variant_label_ref);
+                    const DISCRIMINANT: #repr = #discriminant;
+                    #check_fits
+                    #krate::hash::Hashable::hash_into(&(DISCRIMINANT as #hash_repr), state);
+                    #(#hash_fields_impl)*
+                    state.end();
+                }
+            };
+            let variant_schema = quote! {
+                #krate::schema::VariantSchema {
+                    label: #variant_label,
+                    discriminant: {
+                        const DISCRIMINANT: #repr = #discriminant;
+                        ::std::string::ToString::to_string(&(DISCRIMINANT as #hash_repr))
+                    },
+                    fields: ::std::vec![#(#fields_schema),*],
+                }
+            };
+            Ok((hash_variant_impl, variant_schema))
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .unzip();
+    let schema_impl = expand_hash_schema(
+        ast,
+        &generics,
+        &krate,
+        quote! {
+            #krate::schema::Schema::Enum {
+                label: #type_label,
+                repr: stringify!(#hash_repr),
+                variants: ::std::vec![#(#variants_schema),*],
             }
+        },
+    );
+
+    let hash_impl = quote! {
+        state.begin();
+        state.label(#type_label_ref);
+        match #receiver {
+        #(#hash_variants_impl)*
         }
+        state.end();
+    };
+    let hash_impl = match &container.remote {
+        Some(remote) => expand_remote_hash(ast, &generics, &krate, remote, hash_impl),
+        None => quote! {
+            impl #impl_generics #krate::hash::Hashable for #ident #ty_generics #where_clause {
+                fn hash_into<__H>(&self, state: &mut __H)
+                where
+                    __H: #krate::hash::NizHasher + ?Sized,
+                {
+                    use #krate::hash::NizHasher;
+
+                    #hash_impl
+                }
+            }
+        },
+    };
+    let expanded = quote! {
+        #hash_impl
+
+        #schema_impl
     };
 
-    TokenStream::from(expanded)
+    Ok(expanded)
 }
@@ -1,37 +1,157 @@
-use proc_macro2::TokenTree;
-use syn::Attribute;
+use proc_macro2::{Spacing, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{Attribute, Expr, ExprLit, Ident, Lit, Meta, NestedMeta, Type};
 
-pub(crate) fn has_skip_attr(attrs: &Vec<Attribute>) -> bool {
-    has_any_attr(&["skip"], attrs)
+use crate::attr;
+
+const INT_TYPES: &[&str] = &[
+    "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+];
+
+/// The integer type named in an enum's `#[repr(...)]`, if any.
+pub(crate) fn repr_type(attrs: &[Attribute]) -> Option<Ident> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("repr"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten()
+        .find_map(|nested| match nested {
+            NestedMeta::Meta(Meta::Path(path)) => path
+                .get_ident()
+                .filter(|ident| INT_TYPES.iter().any(|ty| ident == ty))
+                .cloned(),
+            _ => None,
+        })
 }
 
-pub(crate) fn has_json_attr(attrs: &Vec<Attribute>) -> bool {
-    has_any_attr(&["json"], attrs)
+/// The fixed-width integer type that discriminants of type `repr` are hashed
+/// as, so that the hash does not depend on the target's pointer width.
+pub(crate) fn portable_repr_type(repr: &Ident) -> Ident {
+    if repr == "isize" {
+        Ident::new("i64", repr.span())
+    } else if repr == "usize" {
+        Ident::new("u64", repr.span())
+    } else {
+        repr.clone()
+    }
 }
 
-fn has_any_attr(options: &[&str], attrs: &[Attribute]) -> bool {
-    for attr in attrs {
-        if !attr.path.is_ident("niz") {
-            // ignore non-niz attributes
-            continue;
+/// Whether `ty` is written as `[u8; 32]`, the type of a digest.
+pub(crate) fn is_digest_type(ty: &Type) -> bool {
+    match ty {
+        Type::Array(array) => {
+            let is_u8 = matches!(&*array.elem, Type::Path(path) if path.qself.is_none() && path.path.is_ident("u8"));
+            let is_32 = matches!(
+                &array.len,
+                Expr::Lit(ExprLit { lit: Lit::Int(len), .. }) if len.base10_digits() == "32"
+            );
+            is_u8 && is_32
         }
-        // find any of the listed attributes
-        if attr
-            .tokens
-            .clone()
-            .into_iter()
-            .filter_map(|token_tree| match token_tree {
-                TokenTree::Group(group) => Some(group.stream().into_iter()),
-                _ => None,
-            })
-            .flatten()
-            .any(|t| {
-                let s = t.to_string();
-                options.iter().any(|o| &s == o)
-            })
-        {
-            return true;
+        Type::Group(group) => is_digest_type(&group.elem),
+        Type::Paren(paren) => is_digest_type(&paren.elem),
+        _ => false,
+    }
+}
+
+/// Whether `ty` names a map, the usual target of `#[serde(flatten)]` for
+/// catch-all fields.
+pub(crate) fn is_map_type(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path.path.segments.last().is_some_and(|segment| {
+            segment.ident == "BTreeMap" || segment.ident == "HashMap" || segment.ident == "Map"
+        }),
+        Type::Group(group) => is_map_type(&group.elem),
+        Type::Paren(paren) => is_map_type(&paren.elem),
+        _ => false,
+    }
+}
+
+/// Renders a type or path the way rustfmt would write it, e.g. `Vec<u8>`
+/// rather than the `Vec < u8 >` of its token stream.
+pub(crate) fn source_text(tokens: &impl ToTokens) -> String {
+    let tokens = tokens.to_token_stream().to_string();
+    let is_word = |ch: Option<char>| ch.is_some_and(|ch| ch.is_alphanumeric() || ch == '_');
+    let mut name = String::new();
+    let mut chars = tokens.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == ' ' {
+            let prev = name.chars().last();
+            let keep = (is_word(prev) && is_word(chars.peek().copied()))
+                || matches!(prev, Some(',' | ';'));
+            if !keep {
+                continue;
+            }
+        }
+        name.push(ch);
+    }
+    name
+}
+
+/// Collects the type parameters among `params` that are mentioned anywhere in
+/// `tokens` into `used`. Parameters mentioned only inside `PhantomData<...>`
+/// are not collected, since it is hashable whatever its parameter.
+pub(crate) fn collect_type_params<'a>(
+    tokens: TokenStream,
+    params: &[&'a Ident],
+    used: &mut Vec<&'a Ident>,
+) {
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token_tree) = tokens.next() {
+        match token_tree {
+            TokenTree::Group(group) => collect_type_params(group.stream(), params, used),
+            TokenTree::Ident(ident) if ident == "PhantomData" => {
+                if matches!(tokens.peek(), Some(TokenTree::Punct(punct)) if punct.as_char() == '<')
+                {
+                    skip_generic_args(&mut tokens);
+                }
+            }
+            TokenTree::Ident(ident) => {
+                if let Some(param) = params.iter().find(|param| **param == &ident) {
+                    if !used.contains(param) {
+                        used.push(param);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Advances `tokens` past a `<...>` argument list, which it starts at.
+fn skip_generic_args(tokens: &mut impl Iterator<Item = TokenTree>) {
+    let mut depth = 0usize;
+    // the `>` of `->` does not close an argument list
+    let mut after_minus = false;
+    for token_tree in tokens {
+        if let TokenTree::Punct(punct) = &token_tree {
+            match punct.as_char() {
+                '<' => depth += 1,
+                '>' if !after_minus => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                _ => {}
+            }
+            after_minus = punct.as_char() == '-' && punct.spacing() == Spacing::Joint;
+        } else {
+            after_minus = false;
+        }
+    }
+}
+
+/// The distinct profile names used by `fields`, in order of first use.
+pub(crate) fn profile_names<'a>(fields: impl Iterator<Item = &'a attr::Field>) -> Vec<String> {
+    let mut names = Vec::new();
+    for profile in fields.flat_map(|attrs| &attrs.profiles) {
+        let name = profile.value();
+        if !names.contains(&name) {
+            names.push(name);
         }
     }
-    false
+    names
 }
@@ -0,0 +1,83 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use niz::{
+    digest::Sha3_256,
+    hash::{self, Label, NizHasher, NodeHasher},
+    Hashable,
+};
+
+macro_rules! wide {
+    ($($field:ident),*) => {
+        #[derive(Hashable)]
+        struct Wide {
+            $($field: u64),*
+        }
+
+        impl Wide {
+            fn new() -> Self {
+                let mut i = 0;
+                Wide {
+                    $($field: {
+                        i += 1;
+                        i
+                    }),*
+                }
+            }
+        }
+    };
+}
+
+wide!(
+    f00, f01, f02, f03, f04, f05, f06, f07, f08, f09, f10, f11, f12, f13, f14, f15, f16, f17, f18,
+    f19, f20, f21, f22, f23, f24, f25, f26, f27, f28, f29, f30, f31
+);
+
+/// Hashes like `NodeHasher`, but computes every prefix from the label name at
+/// runtime, the way labels were hashed before they were precomputed.
+struct RuntimePrefixes(NodeHasher);
+
+impl NizHasher for RuntimePrefixes {
+    type Digest = Sha3_256;
+
+    fn begin(&mut self) {
+        self.0.begin();
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    fn label(&mut self, label: &Label) {
+        self.0.update(&hash::prefix(label.name()));
+    }
+
+    fn digest(&mut self, digest: &[u8; 32]) {
+        self.0.digest(digest);
+    }
+
+    fn end(&mut self) {
+        self.0.end();
+    }
+}
+
+fn hash_with_runtime_prefixes(value: &impl Hashable) -> [u8; 32] {
+    let mut state = RuntimePrefixes(NodeHasher::new());
+    value.hash_into(&mut state);
+    state.0.finalize()
+}
+
+fn bench_wide_struct(c: &mut Criterion) {
+    let wide = Wide::new();
+    assert_eq!(wide.hash(), hash_with_runtime_prefixes(&wide));
+
+    let mut group = c.benchmark_group("wide_struct");
+    group.bench_function("precomputed_prefixes", |b| {
+        b.iter(|| black_box(&wide).hash())
+    });
+    group.bench_function("runtime_prefixes", |b| {
+        b.iter(|| hash_with_runtime_prefixes(black_box(&wide)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_wide_struct);
+criterion_main!(benches);
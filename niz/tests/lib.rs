@@ -2,6 +2,8 @@ use niz::{
     tiny_keccak::{Hasher, Sha3},
     *,
 };
+use serde::Serialize;
+use std::collections::BTreeMap;
 
 #[derive(Hashable)]
 struct TestStruct {
@@ -14,13 +16,13 @@ struct TestStruct {
     j: String,
 }
 
-#[derive(Copy, Clone, Hashable)]
+#[derive(Hashable)]
 enum TestEnum {
     A,
     B,
 }
 
-#[derive(Copy, Clone, Hashable)]
+#[derive(Hashable)]
 enum TestEnumDiscriminant {
     A = 2,
     B = 5,
@@ -114,7 +116,7 @@ fn test_enum_discriminant() {
     let mut variant_output = [0u8; 32];
     let mut variant_hasher = Sha3::v256();
     variant_hasher.update(&hash::prefix("A"));
-    variant_hasher.update(&2u32.hash());
+    variant_hasher.update(&2u8.hash());
     variant_hasher.finalize(&mut variant_output);
     hasher.update(&variant_output);
 
@@ -131,10 +133,1658 @@ fn test_enum_discriminant() {
     let mut variant_output = [0u8; 32];
     let mut variant_hasher = Sha3::v256();
     variant_hasher.update(&hash::prefix("B"));
-    variant_hasher.update(&5u32.hash());
+    variant_hasher.update(&5u8.hash());
+    variant_hasher.finalize(&mut variant_output);
+    hasher.update(&variant_output);
+
+    hasher.finalize(&mut expected);
+    assert_eq!(actual, expected);
+}
+
+#[derive(Hashable)]
+enum TestEnumData {
+    Unit,
+    Tuple(
+        u32,
+        #[allow(dead_code)]
+        #[niz(skip)]
+        String,
+    ),
+    Struct {
+        a: u32,
+        b: String,
+    },
+}
+
+#[test]
+fn test_enum_data() {
+    let test = TestEnumData::Tuple(7, "skipped".to_string());
+    let actual = test.hash();
+
+    let mut expected = [0u8; 32];
+    let mut hasher = Sha3::v256();
+    hasher.update(&hash::prefix("TestEnumData"));
+
+    let mut variant_output = [0u8; 32];
+    let mut variant_hasher = Sha3::v256();
+    variant_hasher.update(&hash::prefix("Tuple"));
+    variant_hasher.update(&1u8.hash());
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("0"));
+    field_hasher.update(&7u32.hash());
+    field_hasher.finalize(&mut field_output);
+    variant_hasher.update(&field_output);
+
+    variant_hasher.finalize(&mut variant_output);
+    hasher.update(&variant_output);
+
+    hasher.finalize(&mut expected);
+    assert_eq!(actual, expected);
+
+    let test = TestEnumData::Struct {
+        a: 1,
+        b: "b".to_string(),
+    };
+    let actual = test.hash();
+
+    let mut expected = [0u8; 32];
+    let mut hasher = Sha3::v256();
+    hasher.update(&hash::prefix("TestEnumData"));
+
+    let mut variant_output = [0u8; 32];
+    let mut variant_hasher = Sha3::v256();
+    variant_hasher.update(&hash::prefix("Struct"));
+    variant_hasher.update(&2u8.hash());
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("a"));
+    field_hasher.update(&1u32.hash());
+    field_hasher.finalize(&mut field_output);
+    variant_hasher.update(&field_output);
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("b"));
+    field_hasher.update(&"b".hash());
+    field_hasher.finalize(&mut field_output);
+    variant_hasher.update(&field_output);
+
     variant_hasher.finalize(&mut variant_output);
     hasher.update(&variant_output);
 
     hasher.finalize(&mut expected);
     assert_eq!(actual, expected);
+
+    assert_ne!(TestEnumData::Unit.hash(), TestEnum::A.hash());
+}
+
+#[derive(Hashable)]
+struct TestTupleStruct(
+    u32,
+    #[allow(dead_code)]
+    #[niz(skip)]
+    String,
+    String,
+);
+
+#[derive(Hashable)]
+struct TestUnitStruct;
+
+#[test]
+fn test_tuple_struct() {
+    let test = TestTupleStruct(1, "skipped".to_string(), "s".to_string());
+    let actual = test.hash();
+
+    let mut expected = [0u8; 32];
+    let mut hasher = Sha3::v256();
+    hasher.update(&hash::prefix("TestTupleStruct"));
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("0"));
+    field_hasher.update(&1u32.hash());
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("2"));
+    field_hasher.update(&"s".hash());
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    hasher.finalize(&mut expected);
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_unit_struct() {
+    let actual = TestUnitStruct.hash();
+
+    let mut expected = [0u8; 32];
+    let mut hasher = Sha3::v256();
+    hasher.update(&hash::prefix("TestUnitStruct"));
+    hasher.finalize(&mut expected);
+    assert_eq!(actual, expected);
+}
+
+#[derive(Hashable)]
+struct TestGeneric<'a, T>
+where
+    T: Clone,
+{
+    items: Vec<T>,
+    name: &'a str,
+}
+
+#[derive(Hashable)]
+#[niz(bound = "T: Hashable + Copy")]
+struct TestGenericBound<T> {
+    item: T,
+}
+
+#[derive(Hashable)]
+enum TestGenericEnum<T> {
+    Some(T),
+    None,
+}
+
+#[test]
+fn test_generic() {
+    let test = TestGeneric {
+        items: vec![1u32, 2u32],
+        name: "page",
+    };
+    let actual = test.hash();
+
+    let mut expected = [0u8; 32];
+    let mut hasher = Sha3::v256();
+    hasher.update(&hash::prefix("TestGeneric"));
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("items"));
+    field_hasher.update(&vec![1u32, 2u32].hash());
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("name"));
+    field_hasher.update(&"page".hash());
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    hasher.finalize(&mut expected);
+    assert_eq!(actual, expected);
+
+    assert_ne!(
+        TestGenericBound { item: 1u32 }.hash(),
+        TestGenericBound { item: 2u32 }.hash()
+    );
+    assert_ne!(
+        TestGenericEnum::Some(1u32).hash(),
+        TestGenericEnum::<u32>::None.hash()
+    );
+}
+
+/// Implements `HashableFields` but not `Hashable`, so it can only be
+/// flattened.
+struct TestFieldsOnly(u32);
+
+impl HashableFields for TestFieldsOnly {
+    fn hash_fields<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        state.begin();
+        state.label(&Label::new("a"));
+        self.0.hash_into(state);
+        state.end();
+    }
+}
+
+#[derive(Hashable)]
+struct TestGenericFlatten<T> {
+    #[niz(flatten)]
+    inner: T,
+}
+
+/// Not `Hashable`.
+struct TestMarker;
+
+#[derive(Hashable)]
+struct TestGenericPhantom<T> {
+    a: u32,
+    marker: std::marker::PhantomData<T>,
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestGenericFlatten")]
+struct TestGenericFlattenInline {
+    a: u32,
+}
+
+#[test]
+fn test_generic_inferred_bounds() {
+    let flatten = TestGenericFlatten {
+        inner: TestFieldsOnly(1),
+    };
+    assert_eq!(flatten.hash(), TestGenericFlattenInline { a: 1 }.hash());
+
+    let phantom = TestGenericPhantom::<TestMarker> {
+        a: 1,
+        marker: std::marker::PhantomData,
+    };
+    let mut expected = [0u8; 32];
+    let mut hasher = Sha3::v256();
+    hasher.update(&hash::prefix("TestGenericPhantom"));
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("a"));
+    field_hasher.update(&1u32.hash());
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    let mut marker_output = [0u8; 32];
+    let mut marker_hasher = Sha3::v256();
+    marker_hasher.update(&hash::prefix("phantom"));
+    marker_hasher.finalize(&mut marker_output);
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("marker"));
+    field_hasher.update(&marker_output);
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    hasher.finalize(&mut expected);
+    assert_eq!(phantom.hash(), expected);
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestStruct")]
+struct TestStructRenamed {
+    #[niz(rename = "a")]
+    first: u32,
+    b: u32,
+    j: String,
+}
+
+#[test]
+fn test_rename() {
+    let test = TestStructRenamed {
+        first: 1,
+        b: 2,
+        j: "json_string".to_string(),
+    };
+    let original = TestStruct {
+        a: 1,
+        b: 2,
+        c: "c".to_string(),
+        j: "json_string".to_string(),
+    };
+    assert_eq!(test.hash(), original.hash());
+}
+
+struct Foreign(u64);
+
+fn hash_foreign(foreign: &Foreign) -> [u8; 32] {
+    foreign.0.hash()
+}
+
+#[derive(Hashable)]
+struct TestWith {
+    #[niz(with = "hash_foreign")]
+    foreign: Foreign,
+}
+
+#[test]
+fn test_with() {
+    let test = TestWith {
+        foreign: Foreign(42),
+    };
+    let actual = test.hash();
+
+    let mut expected = [0u8; 32];
+    let mut hasher = Sha3::v256();
+    hasher.update(&hash::prefix("TestWith"));
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("foreign"));
+    field_hasher.update(&42u64.hash());
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    hasher.finalize(&mut expected);
+    assert_eq!(actual, expected);
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestRecord")]
+struct TestRecordV1 {
+    a: u32,
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestRecord")]
+struct TestRecordV2 {
+    a: u32,
+    #[niz(skip_if_none)]
+    note: Option<String>,
+    #[niz(skip_if_default)]
+    count: u64,
+    #[niz(skip_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+#[test]
+fn test_skip_if() {
+    let v1 = TestRecordV1 { a: 1 };
+    let v2 = TestRecordV2 {
+        a: 1,
+        note: None,
+        count: 0,
+        tags: vec![],
+    };
+    assert_eq!(v1.hash(), v2.hash());
+
+    let v2 = TestRecordV2 {
+        a: 1,
+        note: Some("note".to_string()),
+        count: 0,
+        tags: vec![],
+    };
+    assert_ne!(v1.hash(), v2.hash());
+
+    let v2 = TestRecordV2 {
+        a: 1,
+        note: None,
+        count: 1,
+        tags: vec![],
+    };
+    assert_ne!(v1.hash(), v2.hash());
+
+    let v2 = TestRecordV2 {
+        a: 1,
+        note: None,
+        count: 0,
+        tags: vec!["tag".to_string()],
+    };
+    assert_ne!(v1.hash(), v2.hash());
+}
+
+#[derive(Hashable, Serialize)]
+struct TestAudit {
+    created_at: u64,
+    created_by: String,
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestDocument")]
+struct TestDocumentFlat {
+    title: String,
+    created_at: u64,
+    created_by: String,
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestDocument")]
+struct TestDocumentNested {
+    title: String,
+    #[niz(flatten)]
+    audit: TestAudit,
+}
+
+#[derive(Hashable)]
+enum TestFlattenEnum {
+    Flat {
+        title: String,
+        created_at: u64,
+        created_by: String,
+    },
+    Nested {
+        title: String,
+        #[niz(flatten)]
+        audit: TestAudit,
+    },
+}
+
+#[test]
+fn test_flatten() {
+    let flat = TestDocumentFlat {
+        title: "title".to_string(),
+        created_at: 1,
+        created_by: "me".to_string(),
+    };
+    let nested = TestDocumentNested {
+        title: "title".to_string(),
+        audit: TestAudit {
+            created_at: 1,
+            created_by: "me".to_string(),
+        },
+    };
+    assert_eq!(flat.hash(), nested.hash());
+
+    let flat = TestFlattenEnum::Flat {
+        title: "title".to_string(),
+        created_at: 1,
+        created_by: "me".to_string(),
+    };
+    let nested = TestFlattenEnum::Nested {
+        title: "title".to_string(),
+        audit: TestAudit {
+            created_at: 1,
+            created_by: "me".to_string(),
+        },
+    };
+    assert_ne!(flat.hash(), nested.hash());
+}
+
+#[derive(Hashable)]
+#[niz(transparent)]
+struct TestEmail(String);
+
+#[derive(Hashable)]
+#[niz(transparent)]
+struct TestWrapper<T> {
+    #[allow(dead_code)]
+    #[niz(skip)]
+    cached: Option<[u8; 32]>,
+    inner: T,
+}
+
+#[test]
+fn test_transparent() {
+    let email = "user@example.com";
+    assert_eq!(TestEmail(email.to_string()).hash(), email.hash());
+    assert_eq!(
+        TestWrapper {
+            cached: None,
+            inner: 42u64,
+        }
+        .hash(),
+        42u64.hash()
+    );
+}
+
+#[derive(Hashable, Serialize)]
+#[serde(rename = "TestDocument", rename_all = "camelCase")]
+#[niz(serde_compat)]
+struct TestSerde {
+    document_title: String,
+    #[allow(dead_code)]
+    #[serde(skip)]
+    cache: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    #[serde(rename = "n")]
+    #[niz(rename = "count")]
+    count: u32,
+    #[serde(flatten)]
+    audit: TestAudit,
+    #[serde(flatten)]
+    #[niz(rename = "extra")]
+    extra: BTreeMap<String, u32>,
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestDocument")]
+struct TestSerdeEquivalent {
+    #[niz(rename = "documentTitle")]
+    document_title: String,
+    #[niz(skip_if_none)]
+    note: Option<String>,
+    count: u32,
+    #[niz(flatten)]
+    audit: TestAudit,
+    extra: BTreeMap<String, u32>,
+}
+
+#[derive(Hashable, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[niz(serde_compat)]
+enum TestSerdeEnum {
+    FirstVariant,
+    #[serde(rename = "second")]
+    SecondVariant,
+}
+
+#[derive(Hashable, Serialize)]
+#[serde(rename_all = "snake_case", rename_all_fields = "camelCase")]
+#[niz(serde_compat)]
+enum TestSerdeStructEnum {
+    FirstVariant {
+        field_name: u32,
+    },
+    #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+    SecondVariant {
+        field_name: u32,
+    },
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestSerdeStructEnum")]
+enum TestSerdeStructEnumEquivalent {
+    #[niz(rename = "first_variant")]
+    FirstVariant {
+        #[niz(rename = "fieldName")]
+        field_name: u32,
+    },
+    #[niz(rename = "second_variant")]
+    SecondVariant {
+        #[niz(rename = "FIELD_NAME")]
+        field_name: u32,
+    },
+}
+
+#[derive(Hashable, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[niz(serde_compat)]
+enum TestSerdeVariantFields {
+    StructVariant { field_name: u32 },
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestSerdeVariantFields")]
+enum TestSerdeVariantFieldsEquivalent {
+    #[niz(rename = "structVariant")]
+    StructVariant { field_name: u32 },
+}
+
+#[test]
+fn test_serde_compat_variant_fields() {
+    let first = TestSerdeStructEnum::FirstVariant { field_name: 1 };
+    assert_eq!(
+        serde_json::to_string(&first).unwrap(),
+        r#"{"first_variant":{"fieldName":1}}"#
+    );
+    assert_eq!(
+        first.hash(),
+        TestSerdeStructEnumEquivalent::FirstVariant { field_name: 1 }.hash()
+    );
+
+    let second = TestSerdeStructEnum::SecondVariant { field_name: 1 };
+    assert_eq!(
+        serde_json::to_string(&second).unwrap(),
+        r#"{"second_variant":{"FIELD_NAME":1}}"#
+    );
+    assert_eq!(
+        second.hash(),
+        TestSerdeStructEnumEquivalent::SecondVariant { field_name: 1 }.hash()
+    );
+
+    // the container's `rename_all` only renames variants
+    let variant = TestSerdeVariantFields::StructVariant { field_name: 1 };
+    assert_eq!(
+        serde_json::to_string(&variant).unwrap(),
+        r#"{"structVariant":{"field_name":1}}"#
+    );
+    assert_eq!(
+        variant.hash(),
+        TestSerdeVariantFieldsEquivalent::StructVariant { field_name: 1 }.hash()
+    );
+}
+
+#[test]
+fn test_serde_compat() {
+    let test = TestSerde {
+        document_title: "title".to_string(),
+        cache: Some("cache".to_string()),
+        note: None,
+        count: 1,
+        audit: TestAudit {
+            created_at: 1,
+            created_by: "me".to_string(),
+        },
+        extra: BTreeMap::from([("tag".to_string(), 2)]),
+    };
+    let equivalent = TestSerdeEquivalent {
+        document_title: "title".to_string(),
+        note: None,
+        count: 1,
+        audit: TestAudit {
+            created_at: 1,
+            created_by: "me".to_string(),
+        },
+        extra: BTreeMap::from([("tag".to_string(), 2)]),
+    };
+    assert_eq!(test.hash(), equivalent.hash());
+
+    let actual = TestSerdeEnum::SecondVariant.hash();
+
+    let mut expected = [0u8; 32];
+    let mut hasher = Sha3::v256();
+    hasher.update(&hash::prefix("TestSerdeEnum"));
+
+    let mut variant_output = [0u8; 32];
+    let mut variant_hasher = Sha3::v256();
+    variant_hasher.update(&hash::prefix("second"));
+    variant_hasher.update(&1u8.hash());
+    variant_hasher.finalize(&mut variant_output);
+    hasher.update(&variant_output);
+
+    hasher.finalize(&mut expected);
+    assert_eq!(actual, expected);
+
+    assert_ne!(
+        TestSerdeEnum::FirstVariant.hash(),
+        TestSerdeEnum::SecondVariant.hash()
+    );
+}
+
+#[derive(Hashable)]
+#[repr(u16)]
+enum TestEnumRepr {
+    A = 300,
+    B,
+    C(String) = 1000,
+}
+
+#[test]
+fn test_enum_repr() {
+    let actual = TestEnumRepr::B.hash();
+
+    let mut expected = [0u8; 32];
+    let mut hasher = Sha3::v256();
+    hasher.update(&hash::prefix("TestEnumRepr"));
+
+    let mut variant_output = [0u8; 32];
+    let mut variant_hasher = Sha3::v256();
+    variant_hasher.update(&hash::prefix("B"));
+    variant_hasher.update(&301u16.hash());
+    variant_hasher.finalize(&mut variant_output);
+    hasher.update(&variant_output);
+
+    hasher.finalize(&mut expected);
+    assert_eq!(actual, expected);
+
+    assert_ne!(
+        TestEnumRepr::A.hash(),
+        TestEnumRepr::C("c".to_string()).hash()
+    );
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestEnum")]
+enum TestEnumWrittenOut {
+    A = 0,
+    B = 1,
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestEnum")]
+enum TestEnumPartlyWrittenOut {
+    A = 0,
+    B,
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestEnumRepr")]
+#[repr(u16)]
+enum TestEnumReprWrittenOut {
+    A = 300,
+    B = 301,
+}
+
+#[test]
+fn test_enum_written_out_discriminant() {
+    assert_eq!(TestEnumWrittenOut::A.hash(), TestEnum::A.hash());
+    assert_eq!(TestEnumWrittenOut::B.hash(), TestEnum::B.hash());
+    assert_eq!(TestEnumPartlyWrittenOut::A.hash(), TestEnum::A.hash());
+    assert_eq!(TestEnumPartlyWrittenOut::B.hash(), TestEnum::B.hash());
+    assert_eq!(TestEnumReprWrittenOut::A.hash(), TestEnumRepr::A.hash());
+    assert_eq!(TestEnumReprWrittenOut::B.hash(), TestEnumRepr::B.hash());
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestEnum")]
+enum TestEnumEvolved {
+    A,
+    #[niz(index = 2)]
+    Inserted,
+    #[niz(rename = "B", index = 1)]
+    Renamed,
+}
+
+#[test]
+fn test_variant_rename_index() {
+    assert_eq!(TestEnumEvolved::A.hash(), TestEnum::A.hash());
+    assert_eq!(TestEnumEvolved::Renamed.hash(), TestEnum::B.hash());
+    assert_ne!(TestEnumEvolved::Inserted.hash(), TestEnum::B.hash());
+}
+
+mod facade {
+    pub use niz as hashing;
+}
+
+#[derive(facade::hashing::Hashable)]
+#[niz(crate = "crate::facade::hashing", name = "TestWith")]
+struct TestCratePath {
+    #[niz(json)]
+    foreign: u64,
+}
+
+#[test]
+fn test_crate_path() {
+    let test = TestCratePath { foreign: 42 };
+    let expected = TestWith {
+        foreign: Foreign(42),
+    };
+    assert_eq!(test.hash(), expected.hash());
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestSorted", sorted_fields)]
+struct TestSortedA {
+    b: u32,
+    #[niz(rename = "a")]
+    first: u32,
+    c: String,
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestSorted", sorted_fields)]
+struct TestSortedB {
+    c: String,
+    b: u32,
+    a: u32,
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestSorted")]
+struct TestSortedDeclared {
+    a: u32,
+    b: u32,
+    c: String,
+}
+
+#[test]
+fn test_sorted_fields() {
+    let a = TestSortedA {
+        b: 2,
+        first: 1,
+        c: "c".to_string(),
+    };
+    let b = TestSortedB {
+        c: "c".to_string(),
+        b: 2,
+        a: 1,
+    };
+    let declared = TestSortedDeclared {
+        a: 1,
+        b: 2,
+        c: "c".to_string(),
+    };
+    assert_eq!(a.hash(), b.hash());
+    assert_eq!(a.hash(), declared.hash());
+}
+
+#[derive(Hashable)]
+struct TestUnordered {
+    #[niz(unordered)]
+    tags: Vec<String>,
+}
+
+#[test]
+fn test_unordered() {
+    let a = TestUnordered {
+        tags: vec!["a".to_string(), "b".to_string(), "a".to_string()],
+    };
+    let b = TestUnordered {
+        tags: vec!["b".to_string(), "a".to_string(), "a".to_string()],
+    };
+    let c = TestUnordered {
+        tags: vec!["a".to_string(), "b".to_string(), "b".to_string()],
+    };
+    assert_eq!(a.hash(), b.hash());
+    assert_ne!(a.hash(), c.hash());
+
+    assert_eq!(
+        adt::hash_unordered([1u32, 2, 3]),
+        adt::hash_unordered([3u32, 1, 2])
+    );
+    assert_ne!(adt::hash_unordered([1u32, 2, 3]), [1u32, 2, 3].hash());
+}
+
+#[derive(Hashable)]
+struct TestProfiles {
+    #[niz(profile = "identity", profile = "summary")]
+    id: u64,
+    #[niz(profile = "summary")]
+    title: String,
+    body: String,
+    #[niz(skip, profile = "identity")]
+    tenant: String,
+}
+
+#[test]
+fn test_profiles() {
+    let test = TestProfiles {
+        id: 7,
+        title: "title".to_string(),
+        body: "body".to_string(),
+        tenant: "tenant".to_string(),
+    };
+    let actual = test.hash_profile("identity");
+
+    let mut expected = [0u8; 32];
+    let mut hasher = Sha3::v256();
+    hasher.update(&hash::prefix("TestProfiles"));
+    hasher.update(&hash::prefix("identity"));
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("id"));
+    field_hasher.update(&7u64.hash());
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("tenant"));
+    field_hasher.update(&"tenant".hash());
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    hasher.finalize(&mut expected);
+    assert_eq!(actual, Some(expected));
+
+    let edited = TestProfiles {
+        id: 7,
+        title: "title".to_string(),
+        body: "edited".to_string(),
+        tenant: "tenant".to_string(),
+    };
+    assert_ne!(edited.hash(), test.hash());
+    assert_eq!(edited.hash_profile("summary"), test.hash_profile("summary"));
+    assert_ne!(test.hash_profile("summary"), test.hash_profile("identity"));
+    assert_eq!(test.hash_profile("unknown"), None);
+}
+
+#[derive(Hashable)]
+struct TestProfilesSkipIf {
+    #[niz(profile = "summary")]
+    id: u64,
+    #[niz(skip_if = "Option::is_none", profile = "summary")]
+    note: Option<String>,
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestProfilesSkipIf")]
+struct TestProfilesSkipIfBase {
+    #[niz(profile = "summary")]
+    id: u64,
+}
+
+#[test]
+fn test_profiles_skip_if() {
+    let base = TestProfilesSkipIfBase { id: 7 };
+    let without_note = TestProfilesSkipIf { id: 7, note: None };
+    assert_eq!(without_note.hash(), base.hash());
+    assert_eq!(
+        without_note.hash_profile("summary"),
+        base.hash_profile("summary")
+    );
+
+    let with_note = TestProfilesSkipIf {
+        id: 7,
+        note: Some("note".to_string()),
+    };
+    assert_ne!(with_note.hash(), base.hash());
+    assert_ne!(
+        with_note.hash_profile("summary"),
+        base.hash_profile("summary")
+    );
+    assert_ne!(with_note.hash_profile("summary"), Some(with_note.hash()));
+}
+
+#[derive(Hashable)]
+struct TestSelfHash {
+    #[niz(self_hash)]
+    id: [u8; 32],
+    a: u32,
+}
+
+#[test]
+fn test_self_hash() {
+    let mut test = TestSelfHash { id: [0; 32], a: 1 };
+    let unsealed = test.hash();
+    assert!(!test.verify_self_hash());
+
+    test.seal();
+    assert_eq!(test.id, unsealed);
+    assert_eq!(test.hash(), unsealed);
+    assert!(test.verify_self_hash());
+
+    test.a = 2;
+    assert!(!test.verify_self_hash());
+}
+
+mod remote {
+    pub struct Point {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    pub enum Shape {
+        Dot(Point),
+        Circle { center: Point, radius: u32 },
+    }
+}
+
+mod native {
+    use niz::Hashable;
+
+    #[derive(Hashable)]
+    pub struct Point {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    #[derive(Hashable)]
+    pub enum Shape {
+        Dot(Point),
+        Circle { center: Point, radius: u32 },
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Hashable)]
+#[niz(remote = "remote::Point")]
+struct PointDef {
+    x: i32,
+    y: i32,
+}
+
+#[allow(dead_code)]
+#[derive(Hashable)]
+#[niz(remote = "remote::Shape")]
+enum ShapeDef {
+    Dot(#[niz(with = "PointDef::hash_into")] remote::Point),
+    Circle {
+        #[niz(with = "PointDef::hash_into")]
+        center: remote::Point,
+        radius: u32,
+    },
+}
+
+#[derive(Hashable)]
+struct TestRemote {
+    #[niz(with = "ShapeDef::hash")]
+    shape: remote::Shape,
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestRemote")]
+struct TestRemoteInto {
+    #[niz(with = "ShapeDef::hash_into")]
+    shape: remote::Shape,
+}
+
+#[derive(Hashable)]
+#[niz(name = "TestRemote")]
+struct TestNative {
+    shape: native::Shape,
+}
+
+#[test]
+fn test_remote() {
+    let test = TestRemote {
+        shape: remote::Shape::Circle {
+            center: remote::Point { x: 1, y: -2 },
+            radius: 3,
+        },
+    };
+    let expected = TestNative {
+        shape: native::Shape::Circle {
+            center: native::Point { x: 1, y: -2 },
+            radius: 3,
+        },
+    };
+    assert_eq!(test.hash(), expected.hash());
+
+    let point = remote::Point { x: 1, y: -2 };
+    let expected = native::Point { x: 1, y: -2 };
+    assert_eq!(PointDef::hash(&point), expected.hash());
+    assert_ne!(
+        ShapeDef::hash(&remote::Shape::Dot(point)),
+        native::Shape::Dot(native::Point { x: 2, y: -2 }).hash()
+    );
+}
+
+#[test]
+fn test_remote_digest() {
+    use niz::digest::Blake3;
+
+    let test = TestRemoteInto {
+        shape: remote::Shape::Circle {
+            center: remote::Point { x: 1, y: -2 },
+            radius: 3,
+        },
+    };
+    let expected = TestNative {
+        shape: native::Shape::Circle {
+            center: native::Point { x: 1, y: -2 },
+            radius: 3,
+        },
+    };
+    assert_eq!(test.hash(), expected.hash());
+    assert_eq!(test.hash_with::<Blake3>(), expected.hash_with::<Blake3>());
+    assert_eq!(test.hash_stream(), expected.hash_stream());
+
+    // a digest function feeds its SHA3-256 digest whatever the hasher
+    let digest = TestRemote {
+        shape: remote::Shape::Dot(remote::Point { x: 1, y: -2 }),
+    };
+    let expected = TestNative {
+        shape: native::Shape::Dot(native::Point { x: 1, y: -2 }),
+    };
+    assert_eq!(digest.hash(), expected.hash());
+    assert_ne!(digest.hash_with::<Blake3>(), expected.hash_with::<Blake3>());
+}
+
+#[allow(dead_code)]
+#[derive(Hashable)]
+#[niz(name = "Schema")]
+struct TestSchema {
+    #[niz(rename = "identifier")]
+    id: u64,
+    #[niz(skip)]
+    cache: String,
+    #[niz(skip_if_none, profile = "summary")]
+    note: Option<String>,
+    #[niz(unordered)]
+    tags: Vec<String>,
+}
+
+#[allow(dead_code)]
+#[derive(Hashable)]
+#[repr(u8)]
+enum TestSchemaEnum {
+    A = 2,
+    B {
+        #[niz(json)]
+        b: String,
+    },
+}
+
+#[allow(dead_code)]
+#[derive(Hashable)]
+struct TestSchemaNested<T> {
+    schema: TestSchema,
+    #[niz(flatten)]
+    audit: TestAudit,
+    generic: T,
+}
+
+#[test]
+fn test_schema() {
+    use niz::schema::*;
+
+    assert_eq!(
+        TestSchema::hash_schema(),
+        Schema::Struct {
+            label: "Schema",
+            fields: vec![
+                FieldSchema {
+                    label: "identifier",
+                    ty: "u64",
+                    nested: None,
+                    encoding: Encoding::Hashable,
+                    skip: Skip::Never,
+                    profiles: vec![],
+                },
+                FieldSchema {
+                    label: "cache",
+                    ty: "String",
+                    nested: None,
+                    encoding: Encoding::Hashable,
+                    skip: Skip::Always,
+                    profiles: vec![],
+                },
+                FieldSchema {
+                    label: "note",
+                    ty: "Option<String>",
+                    nested: None,
+                    encoding: Encoding::Hashable,
+                    skip: Skip::IfNone,
+                    profiles: vec!["summary"],
+                },
+                FieldSchema {
+                    label: "tags",
+                    ty: "Vec<String>",
+                    nested: None,
+                    encoding: Encoding::Unordered,
+                    skip: Skip::Never,
+                    profiles: vec![],
+                },
+            ],
+        }
+    );
+
+    assert_eq!(
+        TestSchemaEnum::hash_schema().to_json(),
+        serde_json::json!({
+            "kind": "enum",
+            "label": "TestSchemaEnum",
+            "repr": "u8",
+            "variants": [
+                { "label": "A", "discriminant": "2", "fields": [] },
+                {
+                    "label": "B",
+                    "discriminant": "3",
+                    "fields": [{
+                        "label": "b",
+                        "type": "String",
+                        "nested": null,
+                        "encoding": "json",
+                        "skip": "never",
+                        "profiles": [],
+                    }],
+                },
+            ],
+        })
+    );
+
+    let nested = match TestSchemaNested::<TestSchema>::hash_schema() {
+        Schema::Struct { fields, .. } => fields,
+        _ => unreachable!(),
+    };
+    assert_eq!(
+        nested[0].nested.map(|schema| schema()),
+        Some(TestSchema::hash_schema())
+    );
+    assert_eq!(
+        nested[1].nested.map(|schema| schema()),
+        Some(TestAudit::hash_schema())
+    );
+    assert!(nested[2].nested.is_none());
+    assert_eq!(
+        nested[0].to_json()["nested"],
+        TestSchema::hash_schema().to_json()
+    );
+}
+
+#[derive(Hashable)]
+struct TestNormalize {
+    #[niz(normalize = "trim", normalize = "lowercase")]
+    handle: String,
+    #[niz(normalize = "nfc")]
+    name: String,
+}
+
+#[test]
+fn test_normalize() {
+    use niz::scalar::{hash_normalized, Normalization};
+
+    let test = TestNormalize {
+        handle: "  Alice ".to_string(),
+        name: "Jose\u{301}".to_string(),
+    };
+    let actual = test.hash();
+
+    let mut expected = [0u8; 32];
+    let mut hasher = Sha3::v256();
+    hasher.update(&hash::prefix("TestNormalize"));
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("handle"));
+    field_hasher.update(&"alice".hash());
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("name"));
+    field_hasher.update(&"Jos\u{e9}".hash());
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    hasher.finalize(&mut expected);
+    assert_eq!(actual, expected);
+
+    assert_eq!(
+        hash_normalized("Jose\u{301}", &[Normalization::Nfc]),
+        hash_normalized("Jos\u{e9}", &[Normalization::Nfc])
+    );
+    assert_ne!(
+        hash_normalized("Jose\u{301}", &[]),
+        hash_normalized("Jos\u{e9}", &[])
+    );
+}
+
+#[derive(Hashable)]
+struct TestQuantize {
+    #[niz(round = 2)]
+    a: f64,
+    #[niz(significant_digits = 3)]
+    b: f32,
+    #[niz(canonical_float)]
+    c: f64,
+}
+
+#[test]
+fn test_quantize() {
+    use niz::scalar::{hash_quantized, Quantization};
+
+    let test = TestQuantize {
+        a: 0.1 + 0.2,
+        b: 12345.0,
+        c: -0.0,
+    };
+    let actual = test.hash();
+
+    let mut expected = [0u8; 32];
+    let mut hasher = Sha3::v256();
+    hasher.update(&hash::prefix("TestQuantize"));
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("a"));
+    field_hasher.update(&0.3f64.hash());
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("b"));
+    field_hasher.update(&12300f32.hash());
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("c"));
+    field_hasher.update(&0f64.hash());
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    hasher.finalize(&mut expected);
+    assert_eq!(actual, expected);
+
+    assert_eq!(
+        hash_quantized(f64::NAN, Quantization::Canonical),
+        hash_quantized(-f64::NAN, Quantization::Canonical)
+    );
+    assert_eq!(
+        hash_quantized(-0.001f64, Quantization::Round(2)),
+        0f64.hash()
+    );
+    assert_eq!(
+        hash_quantized(0.000123456f64, Quantization::SignificantDigits(2)),
+        0.00012f64.hash()
+    );
+    assert_eq!(
+        hash_quantized(f64::INFINITY, Quantization::Round(2)),
+        f64::INFINITY.hash()
+    );
+}
+
+#[test]
+fn test_quantize_halfway() {
+    use niz::scalar::Quantization;
+
+    // exact halfway cases round to even
+    let round = |value: f64, places| Quantization::Round(places).apply(value);
+    assert_eq!(round(0.125, 2), 0.12);
+    assert_eq!(round(0.375, 2), 0.38);
+    assert_eq!(round(2.5, 0), 2.0);
+    assert_eq!(round(3.5, 0), 4.0);
+    assert_eq!(round(-2.5, 0), -2.0);
+    // these are not halfway once parsed: 0.135 is slightly above and 0.145
+    // slightly below
+    assert_eq!(round(0.135, 2), 0.14);
+    assert_eq!(round(0.145, 2), 0.14);
+    assert_eq!(round(0.1, 17), 0.1);
+
+    let significant = |value: f64, digits| Quantization::SignificantDigits(digits).apply(value);
+    assert_eq!(significant(125.0, 2), 120.0);
+    assert_eq!(significant(135.0, 2), 140.0);
+    assert_eq!(significant(0.1 + 0.2, 17), 0.1 + 0.2);
+
+    assert_eq!(Quantization::Round(2).apply(0.125f32), 0.12f32);
+}
+
+#[test]
+#[should_panic(expected = "cannot quantize to more than 17 digits")]
+fn test_quantize_too_many_digits() {
+    niz::scalar::Quantization::Round(18).apply(0.5f64);
+}
+
+#[test]
+#[should_panic(expected = "cannot quantize to 0 significant digits")]
+fn test_quantize_zero_significant_digits() {
+    niz::scalar::Quantization::SignificantDigits(0).apply(0.5f64);
+}
+
+#[derive(Hashable)]
+struct TestTruncate {
+    #[niz(truncate = "ms")]
+    at: chrono::DateTime<chrono::Utc>,
+}
+
+#[test]
+fn test_truncate() {
+    use chrono::{TimeZone, Utc};
+    use niz::scalar::{hash_truncated, TimePrecision};
+
+    let at = Utc.timestamp_opt(1_700_000_000, 120_456_789).unwrap();
+    let test = TestTruncate { at };
+    let actual = test.hash();
+
+    let mut expected = [0u8; 32];
+    let mut hasher = Sha3::v256();
+    hasher.update(&hash::prefix("TestTruncate"));
+
+    let mut val_output = [0u8; 32];
+    let mut val_hasher = Sha3::v256();
+    val_hasher.update(b"2023-11-14T22:13:20.120Z");
+    val_hasher.finalize(&mut val_output);
+
+    let mut datetime_output = [0u8; 32];
+    let mut datetime_hasher = Sha3::v256();
+    datetime_hasher.update(&hash::prefix("datetime"));
+    datetime_hasher.update(&val_output);
+    datetime_hasher.finalize(&mut datetime_output);
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("at"));
+    field_hasher.update(&datetime_output);
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    hasher.finalize(&mut expected);
+    assert_eq!(actual, expected);
+
+    let micros = Utc.timestamp_opt(1_700_000_000, 120_456_000).unwrap();
+    assert_eq!(
+        hash_truncated(&at, TimePrecision::Micros),
+        hash_truncated(&micros, TimePrecision::Micros)
+    );
+    assert_ne!(
+        hash_truncated(&at, TimePrecision::Nanos),
+        hash_truncated(&micros, TimePrecision::Nanos)
+    );
+    let seconds = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+    assert_eq!(
+        hash_truncated(&at, TimePrecision::Seconds),
+        hash_truncated(&seconds, TimePrecision::Seconds)
+    );
+}
+
+struct TestManual(u8);
+
+impl Hashable for TestManual {
+    fn hash_into<H>(&self, state: &mut H)
+    where
+        H: NizHasher + ?Sized,
+    {
+        state.digest(&[self.0; 32]);
+    }
+}
+
+#[derive(Hashable)]
+struct TestStream {
+    a: u32,
+    m: TestManual,
+}
+
+#[test]
+fn test_stream() {
+    let test = TestStream {
+        a: 1,
+        m: TestManual(7),
+    };
+
+    let mut expected = [0u8; 32];
+    let mut hasher = Sha3::v256();
+    hasher.update(&hash::prefix("TestStream"));
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("a"));
+    field_hasher.update(&1u32.hash());
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    let mut field_output = [0u8; 32];
+    let mut field_hasher = Sha3::v256();
+    field_hasher.update(&hash::prefix("m"));
+    field_hasher.update(&[7u8; 32]);
+    field_hasher.finalize(&mut field_output);
+    hasher.update(&field_output);
+
+    hasher.finalize(&mut expected);
+    assert_eq!(test.hash(), expected);
+
+    let mut state = StreamHasher::new();
+    state.begin();
+    state.label(&Label::new("TestStream"));
+    state.begin();
+    state.label(&Label::new("a"));
+    state.begin();
+    state.update(&1u32.to_be_bytes());
+    state.end();
+    state.end();
+    state.begin();
+    state.label(&Label::new("m"));
+    state.digest(&[7u8; 32]);
+    state.end();
+    state.end();
+    assert_eq!(test.hash_stream(), state.finalize());
+    assert_ne!(test.hash_stream(), test.hash());
+
+    let bytes = vec![0u8; 1024];
+    let mut node = NodeHasher::new();
+    bytes.hash_into(&mut node);
+    assert_eq!(node.finalize(), bytes.hash());
+    assert_ne!(bytes.hash_stream(), vec![0u8; 1023].hash_stream());
+}
+
+#[derive(Hashable)]
+struct TestDigest {
+    a: u32,
+    #[niz(unordered)]
+    tags: Vec<String>,
+}
+
+#[test]
+fn test_digest() {
+    use niz::digest::{Blake3, Keccak256, Sha256, Sha3_256};
+
+    let node = |parts: &[&[u8]]| {
+        let mut hasher = blake3::Hasher::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        *hasher.finalize().as_bytes()
+    };
+
+    assert_eq!(
+        7u32.hash_with::<Blake3>(),
+        *blake3::hash(&7u32.to_be_bytes()).as_bytes()
+    );
+    assert_eq!(7u32.hash_with::<Sha3_256>(), 7u32.hash());
+
+    let test = TestDigest {
+        a: 1,
+        tags: vec!["b".to_owned(), "a".to_owned()],
+    };
+    let a = node(&[blake3::hash(b"a").as_bytes(), &1u32.hash_with::<Blake3>()]);
+    let mut tags = [
+        "a".to_owned().hash_with::<Blake3>(),
+        "b".to_owned().hash_with::<Blake3>(),
+    ];
+    tags.sort_unstable();
+    let tags = node(&[
+        blake3::hash(b"tags").as_bytes(),
+        &node(&[
+            blake3::hash(b"unordered_list").as_bytes(),
+            &tags[0],
+            &tags[1],
+        ]),
+    ]);
+    let expected = node(&[blake3::hash(b"TestDigest").as_bytes(), &a, &tags]);
+    assert_eq!(test.hash_with::<Blake3>(), expected);
+
+    let reordered = TestDigest {
+        a: 1,
+        tags: vec!["a".to_owned(), "b".to_owned()],
+    };
+    assert_eq!(test.hash_with::<Blake3>(), reordered.hash_with::<Blake3>());
+
+    {
+        use sha2::Digest as _;
+        let expected: [u8; 32] = sha2::Sha256::digest(7u32.to_be_bytes()).into();
+        assert_eq!(7u32.hash_with::<Sha256>(), expected);
+    }
+
+    let mut expected = [0u8; 32];
+    let mut hasher = niz::tiny_keccak::Keccak::v256();
+    hasher.update(&7u32.to_be_bytes());
+    hasher.finalize(&mut expected);
+    assert_eq!(7u32.hash_with::<Keccak256>(), expected);
+
+    let digests = [
+        test.hash(),
+        test.hash_with::<Keccak256>(),
+        test.hash_with::<Sha256>(),
+        test.hash_with::<Blake3>(),
+    ];
+    for (i, a) in digests.iter().enumerate() {
+        for b in &digests[i + 1..] {
+            assert_ne!(a, b);
+        }
+    }
+}
+
+#[derive(Hashable)]
+struct TestDigestJson {
+    #[niz(json)]
+    value: Option<Vec<u32>>,
+}
+
+#[test]
+fn test_digest_json() {
+    use niz::digest::Blake3;
+    use serde_json::{json, Value};
+
+    assert_eq!(Value::Null.hash_with::<Blake3>(), [0u8; 32]);
+    assert_eq!(Value::Null.hash_with::<Blake3>(), Value::Null.hash());
+    assert_eq!(json!(7).hash_with::<Blake3>(), 7u64.hash_with::<Blake3>());
+
+    let value = json!({ "a": [1, "b", null], "c": true });
+    assert_ne!(value.hash_with::<Blake3>(), value.hash());
+    assert_ne!(value.hash_stream(), value.hash());
+
+    let test = TestDigestJson {
+        value: Some(vec![1, 2]),
+    };
+    assert_ne!(test.hash_with::<Blake3>(), test.hash());
+    let equivalent = TestDigestJson { value: None };
+    assert_ne!(test.hash_with::<Blake3>(), equivalent.hash_with::<Blake3>());
+}
+
+#[test]
+fn test_digest_helpers() {
+    use chrono::{TimeZone, Utc};
+    use niz::{
+        adt::{hash_unordered, hash_unordered_with},
+        digest::{Blake3, Sha3_256},
+        scalar::{
+            hash_normalized, hash_normalized_with, hash_quantized, hash_quantized_with,
+            hash_truncated, hash_truncated_with, Normalization, Quantization, TimePrecision,
+        },
+    };
+
+    let node = |parts: &[&[u8]]| {
+        let mut hasher = blake3::Hasher::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        *hasher.finalize().as_bytes()
+    };
+
+    let test = TestProfiles {
+        id: 7,
+        title: "title".to_string(),
+        body: "body".to_string(),
+        tenant: "tenant".to_string(),
+    };
+    let expected = node(&[
+        blake3::hash(b"TestProfiles").as_bytes(),
+        blake3::hash(b"summary").as_bytes(),
+        &node(&[blake3::hash(b"id").as_bytes(), &7u64.hash_with::<Blake3>()]),
+        &node(&[
+            blake3::hash(b"title").as_bytes(),
+            &"title".hash_with::<Blake3>(),
+        ]),
+    ]);
+    assert_eq!(test.hash_profile_with::<Blake3>("summary"), Some(expected));
+    assert_eq!(
+        test.hash_profile_with::<Sha3_256>("summary"),
+        test.hash_profile("summary")
+    );
+    assert_eq!(test.hash_profile_with::<Blake3>("unknown"), None);
+
+    let mut sealed = TestSelfHash { id: [0; 32], a: 1 };
+    sealed.seal_with::<Blake3>();
+    assert_eq!(sealed.id, sealed.hash_with::<Blake3>());
+    assert!(sealed.verify_self_hash_with::<Blake3>());
+    assert!(!sealed.verify_self_hash());
+
+    let trim_lowercase = [Normalization::Trim, Normalization::Lowercase];
+    assert_eq!(
+        hash_normalized_with::<Blake3>(" Alice ", &trim_lowercase),
+        "alice".hash_with::<Blake3>()
+    );
+    assert_eq!(
+        hash_normalized_with::<Sha3_256>(" Alice ", &trim_lowercase),
+        hash_normalized(" Alice ", &trim_lowercase)
+    );
+
+    assert_eq!(
+        hash_quantized_with::<Blake3, _>(0.125f64, Quantization::Round(2)),
+        0.12f64.hash_with::<Blake3>()
+    );
+    assert_eq!(
+        hash_quantized_with::<Sha3_256, _>(0.125f64, Quantization::Round(2)),
+        hash_quantized(0.125f64, Quantization::Round(2))
+    );
+
+    let at = Utc.timestamp_opt(1_700_000_000, 120_456_789).unwrap();
+    let millis = Utc.timestamp_opt(1_700_000_000, 120_000_000).unwrap();
+    assert_eq!(
+        hash_truncated_with::<Blake3>(&at, TimePrecision::Millis),
+        hash_truncated_with::<Blake3>(&millis, TimePrecision::Millis)
+    );
+    assert_eq!(
+        hash_truncated_with::<Sha3_256>(&at, TimePrecision::Millis),
+        hash_truncated(&at, TimePrecision::Millis)
+    );
+    assert_ne!(
+        hash_truncated_with::<Blake3>(&at, TimePrecision::Millis),
+        hash_truncated(&at, TimePrecision::Millis)
+    );
+
+    assert_eq!(
+        hash_unordered_with::<Blake3, _>([1u32, 2]),
+        hash_unordered_with::<Blake3, _>([2u32, 1])
+    );
+    assert_eq!(
+        hash_unordered_with::<Sha3_256, _>([1u32, 2]),
+        hash_unordered([1u32, 2])
+    );
+    assert_ne!(
+        hash_unordered_with::<Blake3, _>([1u32, 2]),
+        hash_unordered([1u32, 2])
+    );
 }
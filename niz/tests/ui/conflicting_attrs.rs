@@ -0,0 +1,15 @@
+use niz::Hashable;
+
+#[derive(Hashable)]
+struct Conflicting {
+    #[niz(json, with = "hash_a")]
+    a: u32,
+}
+
+#[derive(Hashable)]
+struct ConflictingSkip {
+    #[niz(skip, skip_if_default)]
+    a: u32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use niz::Hashable;
+
+#[derive(Hashable)]
+enum Status {
+    Active = 1,
+    Archived = 300,
+}
+
+fn main() {}
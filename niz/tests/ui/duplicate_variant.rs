@@ -0,0 +1,18 @@
+use niz::Hashable;
+
+#[derive(Hashable)]
+enum DuplicateIndex {
+    #[niz(index = 1)]
+    A,
+    #[niz(index = 1)]
+    B,
+}
+
+#[derive(Hashable)]
+enum DuplicateLabel {
+    A,
+    #[niz(rename = "A")]
+    B,
+}
+
+fn main() {}
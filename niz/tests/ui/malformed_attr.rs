@@ -0,0 +1,15 @@
+use niz::Hashable;
+
+#[derive(Hashable)]
+#[niz(bound = 1)]
+struct Malformed<T> {
+    a: T,
+}
+
+#[derive(Hashable)]
+struct NotAList {
+    #[niz = "skip"]
+    a: u32,
+}
+
+fn main() {}
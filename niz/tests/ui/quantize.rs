@@ -0,0 +1,15 @@
+use niz::Hashable;
+
+#[derive(Hashable)]
+struct TooManyPlaces {
+    #[niz(round = 18)]
+    a: f64,
+}
+
+#[derive(Hashable)]
+struct TooManyDigits {
+    #[niz(significant_digits = 0)]
+    a: f64,
+}
+
+fn main() {}
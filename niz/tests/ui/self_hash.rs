@@ -0,0 +1,10 @@
+use niz::Hashable;
+
+#[derive(Hashable)]
+struct WrongType {
+    a: u32,
+    #[niz(self_hash)]
+    hash: Vec<u8>,
+}
+
+fn main() {}
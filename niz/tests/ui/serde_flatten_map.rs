@@ -0,0 +1,14 @@
+use std::collections::BTreeMap;
+
+use niz::Hashable;
+use serde::Serialize;
+
+#[derive(Hashable, Serialize)]
+#[niz(serde_compat)]
+struct Extensible {
+    id: u32,
+    #[serde(flatten)]
+    extra: BTreeMap<String, u32>,
+}
+
+fn main() {}
@@ -0,0 +1,24 @@
+use niz::Hashable;
+
+#[derive(Hashable)]
+struct Inner {
+    a: u32,
+}
+
+#[derive(Hashable)]
+#[niz(sorted_fields)]
+struct Outer {
+    b: u32,
+    #[niz(flatten)]
+    inner: Inner,
+}
+
+#[derive(Hashable)]
+#[niz(sorted_fields)]
+struct Duplicate {
+    a: u32,
+    #[niz(rename = "a")]
+    b: u32,
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use niz::Hashable;
+
+#[derive(Hashable)]
+#[niz(transparent)]
+struct TwoFields {
+    a: u32,
+    b: u32,
+}
+
+#[derive(Hashable)]
+#[niz(transparent)]
+enum Enum {
+    A(u32),
+}
+
+fn main() {}
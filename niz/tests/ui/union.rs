@@ -0,0 +1,9 @@
+use niz::Hashable;
+
+#[derive(Hashable)]
+union Union {
+    a: u32,
+    b: f32,
+}
+
+fn main() {}
@@ -0,0 +1,27 @@
+use niz::Hashable;
+
+#[derive(Hashable)]
+struct UnknownField {
+    #[niz(skp)]
+    a: u32,
+}
+
+#[derive(Hashable)]
+#[niz(skip)]
+struct UnknownContainer {
+    a: u32,
+}
+
+#[derive(Hashable)]
+enum UnknownVariant {
+    #[niz(json)]
+    A,
+}
+
+#[derive(Hashable)]
+struct Duplicate {
+    #[niz(skip, skip)]
+    a: u32,
+}
+
+fn main() {}
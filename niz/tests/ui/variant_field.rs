@@ -0,0 +1,19 @@
+use niz::Hashable;
+
+#[derive(Hashable)]
+enum SkippedProfile {
+    A {
+        #[niz(skip, profile = "p")]
+        a: u32,
+    },
+}
+
+#[derive(Hashable)]
+enum SelfHash {
+    A {
+        #[niz(self_hash)]
+        hash: [u8; 32],
+    },
+}
+
+fn main() {}